@@ -2,34 +2,23 @@
 pub struct MurphyAnimationDescriptor {
     // TODO: migrate offset to a (X, Y) coordinate system or to at least something that takes 320 pixel width screen
     // into account instead of the 122 positions of the original game
-    animation_coordinates_offset: i16, // word_510F0 -> 0x0DE0 -> seems like an offset from the destination position in dimensions of the original game screen (meaning, you have to divide by 122 to get the Y coordinate, and get module 122 and multiply by 8 to get the X coordinate)
-    animation_coordinates_offset_increment: i16, // word_510F2 -> this increases the offset above frame by frame
-    width: u16,                                  // word_510F4
-    height: u16,                                 // word_510F6
-    animation_index: u16, // word_510F8; -> memory address in someBinaryData_5142E, looks like a list of coordinates of frames in MOVING.DAT
-    speed_x: i16,         // word_510FA; -> applied to Murphy X position... speedX?
-    speed_y: i16,         // word_510FC; -> applied to Murphy Y position... speedY?
-    current_frame: u16, // Not used in the original code, I will use it to keep track of the current animation frame
+    pub animation_coordinates_offset: i16, // word_510F0 -> 0x0DE0 -> seems like an offset from the destination position in dimensions of the original game screen (meaning, you have to divide by 122 to get the Y coordinate, and get module 122 and multiply by 8 to get the X coordinate)
+    pub animation_coordinates_offset_increment: i16, // word_510F2 -> this increases the offset above frame by frame
+    pub width: u16,                                  // word_510F4
+    pub height: u16,                                 // word_510F6
+    pub animation_index: u16, // word_510F8; -> memory address in someBinaryData_5142E, looks like a list of coordinates of frames in MOVING.DAT
+    pub speed_x: i16,         // word_510FA; -> applied to Murphy X position... speedX?
+    pub speed_y: i16,         // word_510FC; -> applied to Murphy Y position... speedY?
+    pub current_frame: u16, // Not used in the original code, I will use it to keep track of the current animation frame
 }
 
 pub struct Point {
-    x: u16,
-    y: u16,
+    pub x: u16,
+    pub y: u16,
 }
 
 #[allow(overflowing_literals)]
-pub const K_MURPHY_ANIMATION_DESCRIPTOR: [MurphyAnimationDescriptor; 51] = [
-    MurphyAnimationDescriptor {
-        // 0
-        animation_coordinates_offset: 0x06ac,           // -> dfe
-        animation_coordinates_offset_increment: 0xff0c, // -> e00
-        width: 0x0002,                                  // -> e02
-        height: 0x0012,                                 // -> e04
-        animation_index: 0,                             // 0x111e, // -> e06
-        speed_x: 0x0000,                                // -> e08
-        speed_y: 0xfffe,                                // -> e0a
-        current_frame: 0x0000,                          // -> e0c
-    },
+pub const K_MURPHY_ANIMATION_DESCRIPTOR: [MurphyAnimationDescriptor; 50] = [
     MurphyAnimationDescriptor {
         // 0
         animation_coordinates_offset: 0x06ac,           // -> dfe
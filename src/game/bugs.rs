@@ -0,0 +1,81 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Bugs (updateBugTiles). A bug looks like a base until it sparks.
+//
+// The state of a bug is a signed frame number, updated every 4 frames. Negative values count down to
+// the next spark, and 0 to K_BUG_SPARK_FRAMES - 1 are the frames of the spark. After a spark, the
+// delay to the next one is taken from the level random number generator, so the random sequence
+// must be consumed exactly like in the original game for demos to replay identically.
+
+use crate::game::game_states::GameStates;
+use crate::game::globals::*;
+use crate::game::sounds::SoundEffect;
+
+pub const K_BUG_SPARK_FRAMES: i8 = 14;
+const K_BUG_FRAME_MASK: u16 = 0x03;
+const K_BUG_MIN_SPARK_DELAY: u8 = 0x20;
+const K_BUG_RANDOM_SPARK_DELAY_MASK: u16 = 0x3F;
+
+/// Tells if the bug with the given state is sparking, and kills Murphy when he touches it
+pub fn is_bug_active(state: u8) -> bool {
+    state as i8 >= 0
+}
+
+impl GameStates {
+    /// Plays the spark animation of the bug at the given position
+    pub fn update_bug(&mut self, position: usize) {
+        if self.tile_at(position) != LevelTileType::Bug as u8 {
+            return;
+        }
+        if self.g_frame_counter & K_BUG_FRAME_MASK != 0 {
+            return;
+        }
+
+        let frame = (self.g_current_level_state[position].state as i8).wrapping_add(1);
+        if frame < 0 {
+            self.g_current_level_state[position].state = frame as u8;
+            return;
+        }
+
+        if frame == 0 && self.is_murphy_around(position) {
+            self.queue_sound_effect(SoundEffect::Bug);
+        }
+
+        if frame < K_BUG_SPARK_FRAMES {
+            self.g_current_level_state[position].state = frame as u8;
+            return;
+        }
+
+        let delay = (self.generate_random_number() & K_BUG_RANDOM_SPARK_DELAY_MASK) as u8
+            + K_BUG_MIN_SPARK_DELAY;
+        self.g_current_level_state[position].state = delay.wrapping_neg();
+    }
+
+    /// Tells if Murphy is in one of the 8 tiles around the given position
+    fn is_murphy_around(&self, position: usize) -> bool {
+        let top_left = position.wrapping_sub(K_LEVEL_WIDTH + 1);
+        (0..3).any(|row| {
+            (0..3).any(|column| {
+                let current = top_left.wrapping_add(row * K_LEVEL_WIDTH + column);
+                current != position
+                    && current < K_LEVEL_SIZE
+                    && self.g_current_level_state[current].tile == LevelTileType::Murphy as u8
+            })
+        })
+    }
+}
@@ -26,11 +26,9 @@ use std::path::Path;
 use crate::game::UserInput;
 
 pub struct DemoManager {
-    pub g_levels_dat_filename: String,
     pub g_sp_demo_filename: String,
     pub g_is_sp_demo_available_to_run: u8,
     pub file_is_demo: bool,
-    pub g_is_playing_demo: bool,
     pub g_demo_current_input: UserInput,
     pub g_demo_current_input_index: u16,
     pub g_demo_current_input_repeater_count: u16,
@@ -44,8 +42,15 @@ pub struct DemoManager {
 
     g_current_demo_level_name: String,
 
-    recording_demo_message: String,
+    pub recording_demo_message: String,
+    pub g_has_demo_recording_failed: bool, // The game panel tells it until the level ends
     pub g_demo_recording_just_started: bool,
+    pub g_is_recording_demo: bool,
+    g_recording_demo_random_seed: u16,
+    g_recording_demo_level_data: [u8; K_LEVEL_DATA_LENGTH],
+    g_recording_demo_data: Vec<u8>, // level number, then the recorded inputs
+    g_recording_demo_current_input: UserInput,
+    g_recording_demo_input_repeater_count: u8,
 
     pub g_selected_original_demo_level_number: usize,
     pub g_selected_original_demo_index: u16,
@@ -59,11 +64,9 @@ pub struct DemoManager {
 impl DemoManager {
     pub fn new() -> DemoManager {
         DemoManager {
-            g_levels_dat_filename: String::from("LEVELS.DAT"),
             g_sp_demo_filename: String::from("00S001-0.SP"),
             g_is_sp_demo_available_to_run: 0,
             file_is_demo: false,
-            g_is_playing_demo: false,
             g_demo_current_input: UserInput::None,
             g_demo_current_input_index: 0,
             g_demo_current_input_repeater_count: 0,
@@ -116,8 +119,15 @@ impl DemoManager {
                 },
             ],
             recording_demo_message: String::from("--- RECORDING DEMO0 ---"),
+            g_has_demo_recording_failed: false,
 
             g_demo_recording_just_started: false,
+            g_is_recording_demo: false,
+            g_recording_demo_random_seed: 0,
+            g_recording_demo_level_data: [0; K_LEVEL_DATA_LENGTH],
+            g_recording_demo_data: Vec::new(),
+            g_recording_demo_current_input: UserInput::None,
+            g_recording_demo_input_repeater_count: 0,
             g_selected_original_demo_level_number: 0,
             g_selected_original_demo_index: 0,
             demo_file_name: String::new(),
@@ -128,36 +138,89 @@ impl DemoManager {
         }
     }
 
-    pub fn get_level_number_from_original_demo_file(&self, file: File, file_length: u64) -> usize {
-        0
+    /// Recognizes the demos of the original game, which don't include their level, by their size
+    /// and their first bytes. Returns their level number with K_ORIGINAL_DEMO_FLAG, or 0 if the
+    /// file isn't one of them.
+    pub fn get_level_number_from_original_demo_file(
+        &mut self,
+        file: &mut File,
+        file_length: u64,
+    ) -> usize {
+        let size_matches = self
+            .k_original_demo_file_sizes
+            .iter()
+            .any(|&size| size as u64 == file_length);
+        if !size_matches {
+            return 0;
+        }
+
+        let mut file_chunk = [0_u8; 4];
+        file.seek(SeekFrom::Start(0)).unwrap();
+        if file.read_exact(&mut file_chunk).is_err() {
+            return 0;
+        }
+
+        match self
+            .k_original_demo_first_file_chunks
+            .iter()
+            .position(|chunk| {
+                chunk.level_number == file_chunk[0] && chunk.first_user_inputs == file_chunk[1..]
+            }) {
+            Some(original_demo_index) => {
+                self.g_selected_original_demo_index = original_demo_index as u16;
+                K_ORIGINAL_DEMO_FLAG | file_chunk[0] as usize
+            }
+            None => 0,
+        }
     }
 
-    pub fn prepare_demo_recording_filename(&mut self) {
-        if self.g_levels_dat_filename[8..10] == String::from("00") {
-            self.g_sp_demo_filename.replace_range(2..4, "--");
-        } else if self.g_levels_dat_filename[8..10] == String::from("AT") {
-            self.g_sp_demo_filename.replace_range(2..4, "00");
+    /// Selects a .SP file to run instead of the main menu. Files longer than a level include a
+    /// demo, which is played on their level.
+    pub fn select_sp_file(&mut self, filename: &str) -> Result<(), String> {
+        let file_length = std::fs::metadata(Path::new(filename))
+            .map_err(|error| format!("Can't read {}: {}", filename, error))?
+            .len();
+        if file_length < K_LEVEL_DATA_LENGTH as u64 {
+            return Err(format!("{} is too short to include a level", filename));
         }
+
+        self.demo_file_name = filename.to_string();
+        Level::from_raw(0, &self.read_sp_level_raw_data()?)
+            .map_err(|error| format!("{}: {}", filename, error))?;
+        self.file_is_demo = file_length > K_LEVEL_DATA_LENGTH as u64;
+        self.g_is_sp_demo_available_to_run = 2;
+        Ok(())
+    }
+
+    /// Reads the level at the beginning of the .SP file being run. The file may have changed since
+    /// it was selected.
+    pub fn read_sp_level_raw_data(&self) -> Result<[u8; K_LEVEL_DATA_LENGTH], String> {
+        let mut file_data = [0_u8; K_LEVEL_DATA_LENGTH];
+        File::open(Path::new(&self.demo_file_name))
+            .and_then(|mut file| file.read_exact(&mut file_data))
+            .map_err(|error| format!("Can't read {}: {}", self.demo_file_name, error))?;
+        Ok(file_data)
     }
 
     pub fn read_demo_files(&mut self) -> u8 {
         self.g_demo_current_input_index = 0;
 
         self.g_demos.demo_first_indices = [0xffff; K_NUMBER_OF_DEMOS + 1];
+        self.g_demos.signatures = [(); K_NUMBER_OF_DEMOS].map(|_| String::new());
         self.word_5A33C = 0;
 
         for i in 0..K_NUMBER_OF_DEMOS {
             self.g_selected_original_demo_level_number = 0;
-            let filename = self.g_demo0_bin_filename.clone();
-            let mut filename = filename.as_str();
 
-            if self.g_is_sp_demo_available_to_run == 1 {
-                filename = self.demo_file_name.as_str();
+            let filename = if self.g_is_sp_demo_available_to_run == 1 {
+                self.demo_file_name.clone()
             } else {
                 let value = ('0' as u8 + i as u8) as char;
                 self.g_demo0_bin_filename
                     .replace_range(4..5, String::from(value).as_str());
-            }
+                format!("{}/{}", RESSOURCES_PATH, self.g_demo0_bin_filename)
+            };
+            let filename = filename.as_str();
 
             let file_path = Path::new(filename);
             match file_path
@@ -170,33 +233,34 @@ impl DemoManager {
             let mut file =
                 File::open(file_path).expect(format!("Error while opening {}", filename).as_str());
 
-            if self.g_is_sp_demo_available_to_run == 1 {
-                // Select demo from command line not implemented
-                //if (gSelectedOriginalDemoFromCommandLineLevelNumber == 0)
-                //        {
-                file.seek(SeekFrom::Start(K_LEVEL_DATA_LENGTH as u64))
-                    .unwrap();
-                //fseek(file, K_LEVEL_DATA_LENGTH, SEEK_SET);
-                // }
-            } else {
-                file.seek(SeekFrom::End(0)).unwrap();
-                let file_size = file.stream_position().unwrap();
-                if file_size < K_LEVEL_DATA_LENGTH as u64 {
-                    // TODO : issue to solve : self.g_selected_original_demo_level_number = self.get_level_number_from_original_demo_file(file, file_size);
-                }
-                file.seek(SeekFrom::Start(0)).unwrap();
-
-                if self.g_selected_original_demo_level_number == 0 {
-                    let mut level_buffer = [0_u8; K_LEVEL_DATA_LENGTH];
-                    let bytes = file.read(&mut level_buffer).unwrap();
-
-                    if bytes < K_LEVEL_DATA_LENGTH {
-                        return i as u8;
-                    }
-                    let level = Level::from_raw(1, level_buffer);
-                    self.g_demos.level[i] = level.clone();
-                    self.g_demo_random_seeds[i] = level.random_seed;
+            // The .SP file given to run is read like a DEMOx.BIN file
+            file.seek(SeekFrom::End(0)).unwrap();
+            let file_size = file.stream_position().unwrap();
+            self.g_selected_original_demo_level_number =
+                self.get_level_number_from_original_demo_file(&mut file, file_size);
+            if self.g_selected_original_demo_level_number == 0
+                && file_size < K_LEVEL_DATA_LENGTH as u64
+            {
+                // Other demos too short to include a level come from original demo collections,
+                // they only start with their level number
+                self.g_selected_original_demo_level_number = K_ORIGINAL_DEMO_FLAG;
+            }
+            file.seek(SeekFrom::Start(0)).unwrap();
+
+            if self.g_selected_original_demo_level_number == 0 {
+                let mut level_buffer = [0_u8; K_LEVEL_DATA_LENGTH];
+                let bytes = file.read(&mut level_buffer).unwrap();
+
+                if bytes < K_LEVEL_DATA_LENGTH {
+                    return i as u8;
                 }
+                // Demos with a level that can't be played are ignored like the ones too short
+                let level = match Level::from_raw(1, &level_buffer) {
+                    Ok(level) => level,
+                    Err(_) => return i as u8,
+                };
+                self.g_demos.level[i] = level.clone();
+                self.g_demo_random_seeds[i] = level.random_seed;
             }
             let mut max_number_of_bytes_to_read = K_MAX_DEMO_INPUT_STEPS as u16 + 1;
             max_number_of_bytes_to_read -= self.g_demo_current_input_index;
@@ -204,21 +268,21 @@ impl DemoManager {
                 max_number_of_bytes_to_read = 0;
             }
 
-            let mut number_of_demo_bytes_read = 0;
-
             if max_number_of_bytes_to_read == 0 {
-                number_of_demo_bytes_read = 0;
-            } else {
-                let mut data_buffer: Vec<u8> = vec![0; max_number_of_bytes_to_read as usize];
-                number_of_demo_bytes_read = file.read(&mut data_buffer).unwrap();
-
-                self.g_demos.demo_data[(self.g_demo_current_input_index as usize)..]
-                    .copy_from_slice(data_buffer.as_slice());
+                return i as u8;
+            }
 
-                if number_of_demo_bytes_read == 0 {
-                    return i as u8;
-                }
+            let mut data_buffer: Vec<u8> = vec![0; max_number_of_bytes_to_read as usize];
+            let mut number_of_demo_bytes_read = file.read(&mut data_buffer).unwrap();
+            if number_of_demo_bytes_read == 0 {
+                return i as u8;
             }
+
+            let first_index = self.g_demo_current_input_index as usize;
+            self.g_demos.demo_data[first_index..(first_index + number_of_demo_bytes_read)]
+                .copy_from_slice(&data_buffer[..number_of_demo_bytes_read]);
+            self.g_demos.signatures[i] =
+                parse_demo_signature(&data_buffer[..number_of_demo_bytes_read]);
             self.g_demos.demo_data[self.g_demo_current_input_index as usize] =
                 self.g_demos.demo_data[self.g_demo_current_input_index as usize] & 0x7f; // this removes the MSB from the levelNumber that was added in the speed fix mods
             let is_zero = self.g_selected_original_demo_level_number == 0;
@@ -229,24 +293,222 @@ impl DemoManager {
                 // This sets the MSB?? maybe the "interpreter" later needs it
             }
 
-            let demo_last_byte_index =
-                self.g_demo_current_input_index as usize + number_of_demo_bytes_read - 1;
-            if demo_last_byte_index == 0xffff // this would mean bx was 0. is this possible?
-                || number_of_demo_bytes_read <= 1 // this means the demo is empty (only has levelNumber or nothing)
-                || self.g_demos.demo_data[demo_last_byte_index] != 0xff
+            // Demos without an end marker end with their data. There is always room for the marker,
+            // as one byte less than demo_data can hold is read.
+            let demo_last_byte_index = first_index + number_of_demo_bytes_read - 1;
+            if number_of_demo_bytes_read <= 1 // this means the demo is empty (only has levelNumber)
+                || self.g_demos.demo_data[demo_last_byte_index] != K_DEMO_END_MARKER
             {
-                if demo_last_byte_index < K_BASE_DEMO_SIZE {
-                    number_of_demo_bytes_read += 1;
-                    self.g_demos.demo_data[demo_last_byte_index + 1] = 0xff;
-                }
+                number_of_demo_bytes_read += 1;
+                self.g_demos.demo_data[demo_last_byte_index + 1] = K_DEMO_END_MARKER;
             }
 
             self.g_demos.demo_first_indices[i] = self.g_demo_current_input_index;
             self.g_demo_current_input_index += number_of_demo_bytes_read as u16;
+
+            if self.g_is_sp_demo_available_to_run == 1 {
+                return 1;
+            }
         }
 
         K_NUMBER_OF_DEMOS as u8
     }
+
+    /// Prepares the demo with the given index to be played. Returns false if there is no such demo,
+    /// or if it doesn't include its level and its level number isn't one of the given number of
+    /// levels of the level set.
+    pub fn prepare_demo_playback(&mut self, demo_index: u16, number_of_levels: usize) -> bool {
+        let demo_first_index = self.g_demos.demo_first_indices[demo_index as usize];
+        if demo_first_index == 0xffff {
+            return false;
+        }
+
+        self.g_selected_original_demo_level_number = 0;
+
+        let demo_level_number = self.g_demos.demo_data[demo_first_index as usize] as u16;
+        let mut final_level_number = demo_index;
+
+        if !self.g_demos.includes_level(demo_index as usize) {
+            if demo_level_number == 0 || demo_level_number as usize > number_of_levels {
+                return false;
+            }
+            final_level_number = demo_level_number;
+            self.g_selected_original_demo_level_number =
+                (self.g_selected_original_demo_level_number & 0xFF00) | final_level_number as usize;
+        }
+        self.g_demo_index_or_demo_level_number = final_level_number;
+
+        let demo_first_index = demo_first_index + 1; // To skip the level number
+        self.g_demo_current_input_index = demo_first_index;
+        self.word_5A33C = demo_first_index;
+        self.g_demo_current_input = UserInput::None;
+        self.g_demo_current_input_repeater_count = 1;
+        true
+    }
+
+    /// Returns the user input of the next frame of the demo being played (simulateDemoInput), or
+    /// None once the end of the demo is reached. Each demo byte holds an input in its low nibble,
+    /// and the number of frames it lasts minus one in its high nibble.
+    pub fn next_demo_input(&mut self) -> Option<UserInput> {
+        if self.g_demo_current_input_repeater_count > 1 {
+            self.g_demo_current_input_repeater_count -= 1;
+            return Some(self.g_demo_current_input);
+        }
+
+        let demo_byte = match self
+            .g_demos
+            .demo_data
+            .get(self.g_demo_current_input_index as usize)
+        {
+            Some(&demo_byte) if demo_byte != K_DEMO_END_MARKER => demo_byte,
+            _ => return None,
+        };
+
+        self.g_demo_current_input_index += 1;
+        self.g_demo_current_input = UserInput::from(demo_byte & 0x0F);
+        self.g_demo_current_input_repeater_count = (demo_byte >> 4) as u16 + 1;
+        Some(self.g_demo_current_input)
+    }
+
+    /// Returns the number of input bytes of the demo being played, from its first input to its end
+    /// marker
+    pub fn number_of_demo_inputs(&self) -> u16 {
+        let first_input_index = self.word_5A33C as usize;
+        self.g_demos.demo_data[first_input_index..]
+            .iter()
+            .position(|&demo_byte| demo_byte == K_DEMO_END_MARKER)
+            .unwrap_or(self.g_demos.demo_data.len() - first_input_index) as u16
+    }
+
+    /// Starts recording the user inputs of the level with the given raw data (recordDemo). The demo
+    /// is played with the given random seed.
+    pub fn start_demo_recording(
+        &mut self,
+        demo_index: u8,
+        level_number: u16,
+        level_data: [u8; K_LEVEL_DATA_LENGTH],
+        random_seed: u16,
+    ) {
+        let demo_digit = String::from((b'0' + demo_index) as char);
+        self.recording_demo_message
+            .replace_range(18..19, demo_digit.as_str());
+        self.g_demo0_bin_filename
+            .replace_range(4..5, demo_digit.as_str());
+        self.g_sp_demo_filename
+            .replace_range(7..8, demo_digit.as_str());
+
+        self.g_recording_demo_random_seed = random_seed;
+        self.g_recording_demo_level_data = level_data;
+        // Like the speed fix versions, the MSB of the level number tells the demo includes its level.
+        // Numbers of levels after the 127th don't fit, which doesn't matter as the level is included.
+        self.g_recording_demo_data = vec![level_number as u8 | K_DEMO_WITH_LEVEL_FLAG];
+        self.g_recording_demo_current_input = UserInput::None;
+        self.g_recording_demo_input_repeater_count = 0;
+        self.g_is_recording_demo = true;
+        self.g_has_demo_recording_failed = false;
+    }
+
+    /// Records the user input of one frame. Inputs are stored like demos are played: the input in
+    /// the low nibble, and the number of frames it lasts minus one in the high nibble.
+    pub fn record_demo_input(&mut self, user_input: UserInput) {
+        if self.g_recording_demo_input_repeater_count > 0
+            && (user_input != self.g_recording_demo_current_input
+                || self.g_recording_demo_input_repeater_count == K_MAX_DEMO_INPUT_REPEATER_COUNT)
+        {
+            self.flush_recorded_demo_input();
+        }
+        self.g_recording_demo_current_input = user_input;
+        self.g_recording_demo_input_repeater_count += 1;
+    }
+
+    fn flush_recorded_demo_input(&mut self) {
+        let demo_byte = ((self.g_recording_demo_input_repeater_count - 1) << 4)
+            | self.g_recording_demo_current_input as u8;
+        self.g_recording_demo_data.push(demo_byte);
+        self.g_recording_demo_input_repeater_count = 0;
+    }
+
+    /// Stops the recording (stopRecordingDemo), and writes the demo both to DEMOx.BIN and to a .SP
+    /// file, followed by the signature from myspsig.txt. The game goes on when they can't be
+    /// written.
+    pub fn stop_demo_recording(&mut self) -> Result<(), String> {
+        self.g_is_recording_demo = false;
+        if self.g_recording_demo_input_repeater_count > 0 {
+            self.flush_recorded_demo_input();
+        }
+
+        let random_seed = self.g_recording_demo_random_seed;
+        let scrambled_speed = K_DEMO_RECORDING_SPEED ^ (random_seed >> 8) as u8;
+        let scrambled_checksum = compute_demo_scrambled_checksum(
+            &self.g_recording_demo_data[1..],
+            random_seed,
+            scrambled_speed,
+        );
+
+        let mut demo_file_data = self.g_recording_demo_level_data.to_vec();
+        demo_file_data[K_LEVEL_DATA_LENGTH - 4] = scrambled_speed;
+        demo_file_data[K_LEVEL_DATA_LENGTH - 3] = scrambled_checksum;
+        demo_file_data[K_LEVEL_DATA_LENGTH - 2..].copy_from_slice(&random_seed.to_le_bytes());
+        demo_file_data.extend_from_slice(&self.g_recording_demo_data);
+        demo_file_data.push(K_DEMO_END_MARKER);
+        demo_file_data.extend_from_slice(&read_demo_signature());
+
+        for filename in [&self.g_demo0_bin_filename, &self.g_sp_demo_filename] {
+            let path = format!("{}/{}", RESSOURCES_PATH, filename);
+            if let Err(error) = std::fs::write(Path::new(&path), &demo_file_data) {
+                self.g_has_demo_recording_failed = true;
+                return Err(format!("Error while writing to {}: {}", path, error));
+            }
+        }
+        Ok(())
+    }
+
+    /// Tells if the demo being played doesn't include its level, which must then be loaded from the
+    /// level list
+    pub fn is_original_demo(&self) -> bool {
+        self.g_selected_original_demo_level_number != 0
+    }
+}
+
+/// Computes the checksum the speed fix versions store in the level of a demo. The demo inputs don't
+/// include the level number nor the final 0xFF.
+pub fn compute_demo_scrambled_checksum(
+    demo_inputs: &[u8],
+    random_seed: u16,
+    scrambled_speed: u8,
+) -> u8 {
+    let number_of_frames = demo_inputs.iter().fold(0_u8, |total, demo_byte| {
+        total.wrapping_add((demo_byte >> 4) + 1)
+    });
+    number_of_frames.wrapping_add(random_seed as u8) ^ scrambled_speed
+}
+
+/// Extracts the signature following the inputs of a demo, if any. The demo data starts with the
+/// level number, and both the inputs and the signature end with 0xFF.
+fn parse_demo_signature(demo_data: &[u8]) -> String {
+    let signature = match demo_data
+        .iter()
+        .skip(1)
+        .position(|&demo_byte| demo_byte == K_DEMO_END_MARKER)
+    {
+        Some(end_of_inputs) => &demo_data[end_of_inputs + 2..],
+        None => return String::new(),
+    };
+    let signature_length = signature
+        .iter()
+        .position(|&signature_byte| signature_byte == K_DEMO_END_MARKER)
+        .unwrap_or(signature.len())
+        .min(K_MAX_DEMO_SIGNATURE_LENGTH);
+    String::from_utf8_lossy(&signature[..signature_length]).to_string()
+}
+
+/// Reads the signature appended to recorded demos, which ends with 0xFF
+fn read_demo_signature() -> Vec<u8> {
+    let path = format!("{}/{}", RESSOURCES_PATH, G_DEMO_SIGNATURE_FILENAME);
+    let mut signature = std::fs::read(Path::new(&path)).unwrap_or_default();
+    signature.truncate(K_MAX_DEMO_SIGNATURE_LENGTH);
+    signature.push(K_DEMO_END_MARKER);
+    signature
 }
 
 // These are literally the first 4 bytes of the original files, used by the spfix version to detect when a demo from
@@ -259,6 +521,20 @@ struct FirstOriginalDemoFileChunk {
 }
 
 const K_MAX_DEMO_INPUT_STEPS: usize = 48648;
+pub const K_DEMO_END_MARKER: u8 = 0xFF;
+pub const K_DEMO_WITH_LEVEL_FLAG: u8 = 0x80;
+pub const K_MAX_DEMO_INPUT_REPEATER_COUNT: u8 = 16;
+pub const K_DEMO_NOT_SAVED_MESSAGE: &str = "--- DEMO NOT SAVED! ---";
+// Slowest speed used while recording, as a SpeedFix value: 0x00 is the fastest and 0x0A the
+// slowest. Values count the delays the SpeedFix adds between frames, and 0x00 is the original
+// Supaplex speed ("DLY:00" in SPFIX63.DOC). This game always runs at that speed,
+// K_GAME_FRAMES_PER_SECOND frames per second, and has no key slowing it down, so its demos are
+// always recorded at 0x00.
+const K_DEMO_RECORDING_SPEED: u8 = 0x00;
+const K_SLOWEST_DEMO_SPEED: u8 = 0x0A;
+// Set in g_selected_original_demo_level_number, with the level number of original demos when they
+// are recognized, while reading a demo that doesn't include its level
+const K_ORIGINAL_DEMO_FLAG: usize = 0xFF00;
 const K_MAX_BASE_DEMO_SIZE: usize = 1 + K_MAX_DEMO_INPUT_STEPS + 1;
 
 // This struct defines the demo format of the original game (previous to the speed fix mods)
@@ -272,8 +548,6 @@ struct BaseDemo {
     pub input_steps: [u8; K_MAX_DEMO_INPUT_STEPS + 1], // of UserInput, finishes with 0xFF
 }
 
-const K_BASE_DEMO_SIZE: usize = 4;
-
 const K_MAX_DEMO_SIGNATURE_LENGTH: usize = 511;
 const K_MAX_DEMO_SIGNATURE_SIZE: usize = K_MAX_DEMO_SIGNATURE_LENGTH + 1;
 
@@ -291,6 +565,7 @@ pub struct Demos {
     pub demo_first_indices: [u16; K_NUMBER_OF_DEMOS + 1], // index of the last byte of all demos (starting at demo-segment:0000). there are 11 words because the end of this "list" is marked with 0xFFFF
     pub demo_data: [u8; 1 + K_MAX_DEMO_INPUT_STEPS + 1], // to fit at least one huge demo with 1 byte for level number, then all the possible steps, then 0xFF
     pub level: Box<[Level; K_NUMBER_OF_DEMOS]>,
+    pub signatures: [String; K_NUMBER_OF_DEMOS], // empty for demos without signature
 }
 
 impl Demos {
@@ -299,6 +574,54 @@ impl Demos {
             demo_first_indices: [0xff; K_NUMBER_OF_DEMOS + 1],
             demo_data: [(); 1 + K_MAX_DEMO_INPUT_STEPS + 1].map(|_| 0),
             level: Box::new([(); K_NUMBER_OF_DEMOS].map(|_| Level::new())),
+            signatures: [(); K_NUMBER_OF_DEMOS].map(|_| String::new()),
         }
     }
+
+    /// Tells if the demo with the given index includes its level, like the demos of .SP files
+    pub fn includes_level(&self, demo_index: usize) -> bool {
+        let first_index = self.demo_first_indices[demo_index];
+        first_index != 0xffff && self.demo_data[first_index as usize] & K_DEMO_WITH_LEVEL_FLAG != 0
+    }
+
+    /// Recomputes the slowest speed and the checksum the speed fix versions store in the level of
+    /// the demo with the given index. Returns None for demos that don't include their level.
+    pub fn validate_checksum(&self, demo_index: usize) -> Option<DemoChecksumValidation> {
+        if !self.includes_level(demo_index) {
+            return None;
+        }
+        let first_index = self.demo_first_indices[demo_index];
+
+        let first_input_index = first_index as usize + 1;
+        let number_of_inputs = self.demo_data[first_input_index..]
+            .iter()
+            .position(|&demo_byte| demo_byte == K_DEMO_END_MARKER)
+            .unwrap_or(0);
+        let demo_inputs = &self.demo_data[first_input_index..first_input_index + number_of_inputs];
+
+        let level = &self.level[demo_index];
+        let slowest_speed = level.scrambled_speed ^ (level.random_seed >> 8) as u8;
+        let computed_checksum =
+            compute_demo_scrambled_checksum(demo_inputs, level.random_seed, level.scrambled_speed);
+
+        Some(DemoChecksumValidation {
+            slowest_speed,
+            stored_checksum: level.scrambled_checksum,
+            computed_checksum,
+        })
+    }
+}
+
+/// Outcome of the checksum validation of a demo. Demos written by Megaplex or edited by hand don't
+/// pass it.
+pub struct DemoChecksumValidation {
+    pub slowest_speed: u8,
+    pub stored_checksum: u8,
+    pub computed_checksum: u8,
+}
+
+impl DemoChecksumValidation {
+    pub fn is_valid(&self) -> bool {
+        self.stored_checksum == self.computed_checksum && self.slowest_speed <= K_SLOWEST_DEMO_SPEED
+    }
 }
@@ -0,0 +1,314 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Text format of demo files, to read and edit the moves of a demo by hand. Converting a demo to
+// text and back gives the same file, byte for byte, except for demos without an end marker which
+// get one so they can be played.
+//
+// # Comments start with a hash
+// level-number: 0x81
+// level:
+//   <the 1536 bytes of the level in hexadecimal, absent for original demos>
+// signature: "text after the inputs, with \r, \n, \" and \xHH escapes"
+// inputs:
+//        0  R x12, SpaceU
+//       13  U x3
+//
+// Each input lasts one frame, or the given number of frames. The number starting a line is the
+// frame the first input of the line starts at, it is only there to help reading the demo.
+// Inputs lasting more than 16 frames are split in several demo bytes when compiling the text.
+
+use std::fmt::Write;
+
+use crate::game::demo::{K_DEMO_END_MARKER, K_MAX_DEMO_INPUT_REPEATER_COUNT};
+use crate::game::globals::*;
+use crate::game::level::Level;
+use crate::game::utils::{escape_text, unescape_text};
+
+// Names of the inputs, indexed by the low nibble of the demo bytes
+const K_DEMO_INPUT_NAMES: [&str; 10] = [
+    "N", "U", "L", "D", "R", "SpaceU", "SpaceL", "SpaceD", "SpaceR", "Space",
+];
+const K_LEVEL_BYTES_PER_LINE: usize = 32;
+
+/// Converts the content of a .SP or DEMOx.BIN file into text
+pub fn demo_to_text(demo_file_data: &[u8]) -> Result<String, String> {
+    // Demos from the original game don't include their level
+    let (level_data, demo_data) = if demo_file_data.len() > K_LEVEL_DATA_LENGTH {
+        demo_file_data.split_at(K_LEVEL_DATA_LENGTH)
+    } else if demo_file_data.len() < K_LEVEL_DATA_LENGTH && !demo_file_data.is_empty() {
+        (&demo_file_data[..0], demo_file_data)
+    } else {
+        return Err(String::from("The file doesn't include any demo"));
+    };
+
+    let mut text = String::from("# Rustaplex demo\n");
+    if !level_data.is_empty() {
+        let level = Level::from_raw(0, level_data)?;
+        writeln!(text, "# Level: {}", level.name).unwrap();
+        writeln!(text, "# Random seed: 0x{:04X}", level.random_seed).unwrap();
+    }
+    writeln!(text, "level-number: 0x{:02X}", demo_data[0]).unwrap();
+
+    if !level_data.is_empty() {
+        text.push_str("level:\n");
+        for line in level_data.chunks(K_LEVEL_BYTES_PER_LINE) {
+            text.push_str("  ");
+            for byte in line {
+                write!(text, "{:02X}", byte).unwrap();
+            }
+            text.push('\n');
+        }
+    }
+
+    let demo_inputs = &demo_data[1..];
+    let end_of_inputs = demo_inputs
+        .iter()
+        .position(|&demo_byte| demo_byte == K_DEMO_END_MARKER);
+    match end_of_inputs {
+        Some(end_of_inputs) => {
+            let signature = &demo_inputs[end_of_inputs + 1..];
+            if !signature.is_empty() {
+                writeln!(text, "signature: {}", escape_text(signature)).unwrap();
+            }
+        }
+        None => text.push_str("# The demo has no end marker, compiling adds it\n"),
+    }
+
+    text.push_str("inputs:\n");
+    let mut frame = 0;
+    for demo_byte in &demo_inputs[..end_of_inputs.unwrap_or(demo_inputs.len())] {
+        let number_of_frames = (demo_byte >> 4) as u32 + 1;
+        let input_name = match K_DEMO_INPUT_NAMES.get((demo_byte & 0x0F) as usize) {
+            Some(input_name) => input_name.to_string(),
+            None => format!("#{}", demo_byte & 0x0F),
+        };
+        if number_of_frames == 1 {
+            writeln!(text, "{:>8}  {}", frame, input_name).unwrap();
+        } else {
+            writeln!(text, "{:>8}  {} x{}", frame, input_name, number_of_frames).unwrap();
+        }
+        frame += number_of_frames;
+    }
+    writeln!(text, "# {} frames", frame).unwrap();
+
+    Ok(text)
+}
+
+/// Compiles a demo written as text into the content of a .SP or DEMOx.BIN file
+pub fn text_to_demo(text: &str) -> Result<Vec<u8>, String> {
+    let mut level_number = None;
+    let mut level_data = Vec::new();
+    let mut signature = Vec::new();
+    let mut demo_inputs = Vec::new();
+
+    let mut section = "";
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim() {
+                "level-number" => {
+                    level_number = Some(parse_byte(value).ok_or_else(|| {
+                        format!("Line {}: invalid level number {}", line_number, value)
+                    })?);
+                    continue;
+                }
+                "signature" => {
+                    signature = unescape_text(value)
+                        .map_err(|error| format!("Line {}: {}", line_number, error))?;
+                    continue;
+                }
+                "level" => {
+                    section = "level";
+                    continue;
+                }
+                "inputs" => {
+                    section = "inputs";
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        match section {
+            "level" => {
+                if line.len() % 2 != 0 {
+                    return Err(format!("Line {}: invalid level data", line_number));
+                }
+                for index in (0..line.len()).step_by(2) {
+                    let byte = line
+                        .get(index..index + 2)
+                        .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                        .ok_or_else(|| format!("Line {}: invalid level data", line_number))?;
+                    level_data.push(byte);
+                }
+            }
+            "inputs" => parse_input_line(line, &mut demo_inputs)
+                .map_err(|error| format!("Line {}: {}", line_number, error))?,
+            _ => return Err(format!("Line {}: unexpected text {}", line_number, line)),
+        }
+    }
+
+    if !level_data.is_empty() && level_data.len() != K_LEVEL_DATA_LENGTH {
+        return Err(format!(
+            "The level has {} bytes instead of {}",
+            level_data.len(),
+            K_LEVEL_DATA_LENGTH
+        ));
+    }
+    let level_number = level_number.ok_or("The level number is missing")?;
+
+    let mut demo_file_data = level_data;
+    demo_file_data.push(level_number);
+    demo_file_data.extend_from_slice(&demo_inputs);
+    demo_file_data.push(K_DEMO_END_MARKER);
+    demo_file_data.extend_from_slice(&signature);
+    Ok(demo_file_data)
+}
+
+/// Parses inputs like "R x12, SpaceU", optionally starting with a frame number
+fn parse_input_line(line: &str, demo_inputs: &mut Vec<u8>) -> Result<(), String> {
+    let line = line.trim_start_matches(|character: char| character.is_ascii_digit());
+    for input in line.split(',') {
+        let mut words = input.split_whitespace();
+        let input_name = match words.next() {
+            Some(input_name) => input_name,
+            None => continue,
+        };
+        let input_value = match K_DEMO_INPUT_NAMES
+            .iter()
+            .position(|&name| name == input_name)
+        {
+            Some(input_value) => input_value as u8,
+            None => input_name
+                .strip_prefix('#')
+                .and_then(|value| value.parse::<u8>().ok())
+                .filter(|&value| value < 0x10)
+                .ok_or_else(|| format!("unknown input {}", input_name))?,
+        };
+        let mut number_of_frames = match words.next() {
+            Some(count) => count
+                .strip_prefix('x')
+                .and_then(|count| count.parse::<u32>().ok())
+                .filter(|&count| count > 0)
+                .ok_or_else(|| format!("invalid number of frames {}", count))?,
+            None => 1,
+        };
+        if let Some(word) = words.next() {
+            return Err(format!("unexpected text {}", word));
+        }
+
+        while number_of_frames > 0 {
+            let frames = number_of_frames.min(K_MAX_DEMO_INPUT_REPEATER_COUNT as u32);
+            let demo_byte = ((frames as u8 - 1) << 4) | input_value;
+            if demo_byte == K_DEMO_END_MARKER {
+                return Err(format!("{} x{} would end the demo", input_name, frames));
+            }
+            demo_inputs.push(demo_byte);
+            number_of_frames -= frames;
+        }
+    }
+    Ok(())
+}
+
+fn parse_byte(value: &str) -> Option<u8> {
+    match value.strip_prefix("0x") {
+        Some(value) => u8::from_str_radix(value, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn resource_path(filename: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            RESSOURCES_PATH,
+            filename
+        )
+    }
+
+    #[test]
+    fn shipped_demos_are_converted_to_text_and_back_identically() {
+        for demo_index in 0..10 {
+            let demo_file_data =
+                fs::read(resource_path(&format!("DEMO{}.BIN", demo_index))).unwrap();
+            let text = demo_to_text(&demo_file_data).unwrap();
+            assert_eq!(
+                text_to_demo(&text).unwrap(),
+                demo_file_data,
+                "DEMO{}",
+                demo_index
+            );
+        }
+    }
+
+    #[test]
+    fn sp_demo_with_signature_is_converted_to_text_and_back_identically() {
+        let levels_dat = fs::read(resource_path("LEVELS.DAT")).unwrap();
+        let mut demo_file_data = levels_dat[..K_LEVEL_DATA_LENGTH].to_vec();
+        demo_file_data.extend_from_slice(&[0x81, 0x00, 0xF4, 0x21, 0x09, K_DEMO_END_MARKER]);
+        demo_file_data.extend_from_slice(b"Played by \"me\"\r\n\x01");
+        demo_file_data.push(K_DEMO_END_MARKER);
+
+        let text = demo_to_text(&demo_file_data).unwrap();
+        assert!(text.contains("signature: "));
+        assert!(text.contains("R x16"));
+        assert_eq!(text_to_demo(&text).unwrap(), demo_file_data);
+    }
+
+    #[test]
+    fn demo_without_end_marker_gets_one() {
+        let text = demo_to_text(&[0x01, 0x20, 0x04]).unwrap();
+        assert_eq!(
+            text_to_demo(&text).unwrap(),
+            [0x01, 0x20, 0x04, K_DEMO_END_MARKER]
+        );
+    }
+
+    #[test]
+    fn invalid_demo_texts_are_rejected() {
+        let invalid_texts = [
+            (
+                "level-number: 1\ninputs:\n  0  Up\n",
+                "Line 3: unknown input Up",
+            ),
+            (
+                "level-number: 1\ninputs:\n  0  R x0\n",
+                "Line 3: invalid number of frames x0",
+            ),
+            (
+                "level-number: 1\nlevel:\n  0A0\n",
+                "Line 3: invalid level data",
+            ),
+        ];
+
+        for (text, error) in invalid_texts {
+            assert_eq!(text_to_demo(text), Err(error.to_string()), "{}", text);
+        }
+    }
+}
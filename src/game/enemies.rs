@@ -0,0 +1,211 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Snik snaks and electrons (updateSnikSnakTiles and updateElectronTiles). Both follow the wall on
+// their left, and only differ by their graphics and what they leave when they explode.
+//
+// The state byte is also the index of the animation frame:
+// - 0x00-0x07: turning left, even values face up, left, down and right
+// - 0x08-0x0F: turning right, even values face up, right, down and left
+// - 0x10-0x17, 0x18-0x1F, 0x20-0x27, 0x28-0x2F: moving up, left, down and right. Like the other
+//   objects, enemies move into their destination tile when the movement starts, and the tile they
+//   leave is marked with the direction they go to (see enemy_leaving_word).
+
+use crate::game::game_states::GameStates;
+use crate::game::globals::*;
+use crate::game::simulation::{adjacent_position, Direction, K_ENEMY_LEAVING_TILE};
+
+pub const K_ENEMY_TURNING_LEFT_STATE: u8 = 0x00;
+pub const K_ENEMY_TURNING_RIGHT_STATE: u8 = 0x08;
+pub const K_ENEMY_MOVING_UP_STATE: u8 = 0x10;
+pub const K_ENEMY_MOVING_LEFT_STATE: u8 = 0x18;
+pub const K_ENEMY_MOVING_DOWN_STATE: u8 = 0x20;
+pub const K_ENEMY_MOVING_RIGHT_STATE: u8 = 0x28;
+const K_ENEMY_MOVE_FRAMES: u8 = 8;
+// Frame of the move at which the tile the enemy leaves is released
+const K_ENEMY_LEAVING_FRAME: u8 = 7;
+
+// Enemies rotate one eighth of a turn every 4 frames, and can only leave on the last of them
+const K_ENEMY_TURN_FRAME_MASK: u16 = 0x03;
+
+// States of Murphy going through a port, when enemies don't kill him
+const K_MURPHY_PASSING_PORT_STATES: [u8; 4] = [0x18, 0x19, 0x1A, 0x1B];
+
+/// Word of the tile an enemy is leaving in the given direction
+fn enemy_leaving_word(direction: Direction) -> u16 {
+    (((direction as u16) + 1) << 8) | K_ENEMY_LEAVING_TILE as u16
+}
+
+fn moving_state(direction: Direction) -> u8 {
+    match direction {
+        Direction::Up => K_ENEMY_MOVING_UP_STATE,
+        Direction::Left => K_ENEMY_MOVING_LEFT_STATE,
+        Direction::Down => K_ENEMY_MOVING_DOWN_STATE,
+        Direction::Right => K_ENEMY_MOVING_RIGHT_STATE,
+    }
+}
+
+fn turn_left(direction: Direction) -> Direction {
+    match direction {
+        Direction::Up => Direction::Left,
+        Direction::Left => Direction::Down,
+        Direction::Down => Direction::Right,
+        Direction::Right => Direction::Up,
+    }
+}
+
+fn turn_right(direction: Direction) -> Direction {
+    turn_left(turn_left(turn_left(direction)))
+}
+
+/// Direction an enemy faces at the given turning state, if it faces one
+fn facing_direction(state: u8) -> Option<Direction> {
+    if state & 1 != 0 {
+        return None;
+    }
+    let turning_left = [
+        Direction::Up,
+        Direction::Left,
+        Direction::Down,
+        Direction::Right,
+    ];
+    let turning_right = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+    let index = ((state & 0x07) / 2) as usize;
+    if state < K_ENEMY_TURNING_RIGHT_STATE {
+        Some(turning_left[index])
+    } else {
+        Some(turning_right[index])
+    }
+}
+
+/// First turning state after an enemy moving in the given direction starts turning
+fn turning_state(direction: Direction, is_turning_left: bool) -> u8 {
+    if is_turning_left {
+        K_ENEMY_TURNING_LEFT_STATE + direction as u8 * 2 + 1
+    } else {
+        // Up, Right, Down, Left
+        K_ENEMY_TURNING_RIGHT_STATE + ((4 - direction as u8) & 0x03) * 2 + 1
+    }
+}
+
+impl GameStates {
+    /// Updates the snik snak or electron at the given position
+    pub fn update_enemy(&mut self, position: usize, tile: u8) {
+        if self.g_are_enemies_frozen || self.tile_at(position) != tile {
+            return;
+        }
+
+        let state = self.g_current_level_state[position].state;
+        if state < K_ENEMY_MOVING_UP_STATE {
+            self.update_turning_enemy(position, state);
+        } else if state < K_ENEMY_MOVING_RIGHT_STATE + K_ENEMY_MOVE_FRAMES {
+            self.update_moving_enemy(position, state);
+        }
+    }
+
+    fn update_turning_enemy(&mut self, position: usize, state: u8) {
+        let frame_phase = self.g_frame_counter & K_ENEMY_TURN_FRAME_MASK;
+        if frame_phase == 0 {
+            let base = state & K_ENEMY_TURNING_RIGHT_STATE;
+            self.set_state(position, base | ((state + 1) & 0x07));
+            return;
+        }
+        if frame_phase != K_ENEMY_TURN_FRAME_MASK {
+            return;
+        }
+
+        // The enemy leaves as soon as it faces a free tile
+        let Some(direction) = facing_direction(state) else {
+            return;
+        };
+        let destination = adjacent_position(position, direction);
+        let destination_word = self.word_at(destination);
+        if destination_word == 0 {
+            self.start_enemy_move(position, direction);
+        } else if self.is_murphy_in_reach(destination_word) {
+            self.detonate(position);
+        }
+    }
+
+    fn update_moving_enemy(&mut self, position: usize, state: u8) {
+        let phase = state & 0xF8;
+        let frame = (state & 0x07) + 1;
+        let direction = match phase {
+            K_ENEMY_MOVING_UP_STATE => Direction::Up,
+            K_ENEMY_MOVING_LEFT_STATE => Direction::Left,
+            K_ENEMY_MOVING_DOWN_STATE => Direction::Down,
+            _ => Direction::Right,
+        };
+
+        if frame == K_ENEMY_LEAVING_FRAME {
+            let source = adjacent_position(position, turn_left(turn_left(direction)));
+            self.clear_tile_unless_exploding(source);
+        }
+        if frame < K_ENEMY_MOVE_FRAMES {
+            self.set_state(position, phase + frame);
+            return;
+        }
+
+        // Follow the wall on the left: turn left if there is no wall anymore (or Murphy is there),
+        // keep going while possible, and turn right otherwise.
+        self.set_state(position, 0);
+        let left_word = self.word_at(adjacent_position(position, turn_left(direction)));
+        if left_word == 0 || left_word & 0xFF == LevelTileType::Murphy as u16 {
+            self.set_state(position, turning_state(direction, true));
+            return;
+        }
+
+        let ahead_word = self.word_at(adjacent_position(position, direction));
+        if ahead_word == 0 {
+            self.start_enemy_move(position, direction);
+            return;
+        }
+        if ahead_word & 0xFF == LevelTileType::Murphy as u16 {
+            self.detonate(position);
+            return;
+        }
+
+        let right_word = self.word_at(adjacent_position(position, turn_right(direction)));
+        if right_word == 0 || right_word & 0xFF == LevelTileType::Murphy as u16 {
+            self.set_state(position, turning_state(direction, false));
+        } else {
+            self.set_state(position, turning_state(direction, true));
+        }
+    }
+
+    /// Tells if an enemy facing the tile with the given word kills Murphy. Murphy can't be
+    /// reached while he goes through a port.
+    fn is_murphy_in_reach(&self, word: u16) -> bool {
+        word & 0xFF == LevelTileType::Murphy as u16
+            && !K_MURPHY_PASSING_PORT_STATES.contains(&((word >> 8) as u8))
+    }
+
+    fn start_enemy_move(&mut self, position: usize, direction: Direction) {
+        let tile = self.g_current_level_state[position].tile;
+        let destination = adjacent_position(position, direction);
+        self.set_word(position, enemy_leaving_word(direction));
+        self.g_current_level_state[destination] = StatefulLevelTile {
+            tile,
+            state: moving_state(direction),
+        };
+    }
+}
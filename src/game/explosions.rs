@@ -0,0 +1,208 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Explosions (detonateBigExplosion, updateExplosionTiles and updateExplosionTimers).
+//
+// An explosion turns the 3x3 tiles around its center into explosion tiles, except hardware. The
+// state of an explosion tile is its animation frame, plus K_INFOTRON_EXPLOSION_FLAG when it leaves
+// an infotron (explosions of electrons). Explosive objects caught in an explosion detonate in turn
+// when their timer in g_explosion_timers runs out. Timers are signed: negative timers count up
+// and make an electron explode, positive ones count down.
+
+use crate::game::falling_objects::{K_FALLING_STATE, K_ROLLING_LEFT_STATE, K_ROLLING_RIGHT_STATE};
+use crate::game::game_states::GameStates;
+use crate::game::globals::*;
+use crate::game::simulation::{tile_word, K_FALLING_DESTINATION_WORD};
+use crate::game::sounds::SoundEffect;
+
+pub const K_INFOTRON_EXPLOSION_FLAG: u8 = 0x80;
+const K_EXPLOSION_FRAMES: u8 = 8;
+const K_INFOTRON_EXPLOSION_FRAMES: u8 = 9;
+// Frames before an explosive object caught in an explosion detonates
+const K_CHAIN_EXPLOSION_DELAY: u8 = 0x0D;
+
+// Movement phases of the zonks and infotrons, which free the tiles they were moving from or into
+// when they explode
+const K_ABOUT_TO_ROLL_LEFT_STATE: u8 = 0x50;
+const K_ABOUT_TO_ROLL_RIGHT_STATE: u8 = 0x60;
+const K_LANDED_STATE: u8 = 0x70;
+
+impl GameStates {
+    /// Makes the 3x3 tiles around the given position explode (detonateBigExplosion)
+    pub fn detonate(&mut self, position: usize) {
+        if position >= K_LEVEL_SIZE {
+            return;
+        }
+
+        let center = self.tile_at(position);
+        if center == LevelTileType::Hardware as u8 {
+            return;
+        }
+        self.g_is_explosion_started = true;
+
+        // Electrons leave infotrons, and make the explosive objects around detonate like them
+        let (explosion_word, timer) = if center == LevelTileType::Electron as u8 {
+            (
+                tile_word(LevelTileType::Explosion, K_INFOTRON_EXPLOSION_FLAG),
+                K_CHAIN_EXPLOSION_DELAY.wrapping_neg(),
+            )
+        } else {
+            (
+                tile_word(LevelTileType::Explosion, 0),
+                K_CHAIN_EXPLOSION_DELAY,
+            )
+        };
+
+        let top_left = position.wrapping_sub(K_LEVEL_WIDTH + 1);
+        for row in 0..3 {
+            for column in 0..3 {
+                let current = top_left.wrapping_add(row * K_LEVEL_WIDTH + column);
+                if current == position {
+                    self.set_word(position, explosion_word);
+                } else {
+                    self.explode_tile(current, explosion_word, timer);
+                }
+            }
+        }
+
+        self.queue_sound_effect(SoundEffect::Explosion);
+    }
+
+    fn explode_tile(&mut self, position: usize, explosion_word: u16, timer: u8) {
+        if position >= K_LEVEL_SIZE {
+            return;
+        }
+
+        let tile = self.tile_at(position);
+        match tile {
+            t if t == LevelTileType::Hardware as u8 => {}
+            t if t == LevelTileType::OrangeDisk as u8
+                || t == LevelTileType::YellowDisk as u8
+                || t == LevelTileType::RedDisk as u8
+                || t == LevelTileType::SnikSnak as u8 =>
+            {
+                self.g_explosion_timers[position] = timer;
+                self.set_word(position, explosion_word);
+            }
+            t if t == LevelTileType::Zonk as u8 || t == LevelTileType::Infotron as u8 => {
+                self.explode_falling_object(position, explosion_word);
+            }
+            t if t == LevelTileType::Electron as u8 => {
+                self.g_explosion_timers[position] = timer.wrapping_neg();
+                self.set_tile(
+                    position,
+                    LevelTileType::Explosion,
+                    K_INFOTRON_EXPLOSION_FLAG,
+                );
+            }
+            t if t == LevelTileType::Murphy as u8 => {
+                self.g_should_kill_murphy = true;
+                self.g_explosion_timers[position] = timer;
+                self.set_word(position, explosion_word);
+            }
+            _ => self.set_word(position, explosion_word),
+        }
+    }
+
+    /// Makes a zonk or infotron explode, and frees the tiles it was moving from or into
+    /// (explodeZonk and explodeInfotron)
+    fn explode_falling_object(&mut self, position: usize, explosion_word: u16) {
+        let phase = self.g_current_level_state[position].state & 0xF0;
+        self.set_word(position, explosion_word);
+
+        let above = position.wrapping_sub(K_LEVEL_WIDTH);
+        let below = position + K_LEVEL_WIDTH;
+        match phase {
+            K_FALLING_STATE | K_LANDED_STATE => {
+                self.clear_tile_unless_exploding(above);
+                if self.word_at(below) == K_FALLING_DESTINATION_WORD {
+                    self.clear_tile_unless_exploding(below);
+                }
+            }
+            K_ROLLING_LEFT_STATE => {
+                self.clear_tile_unless_exploding(position + 1);
+                self.clear_tile_unless_exploding(below);
+            }
+            K_ROLLING_RIGHT_STATE => {
+                self.clear_tile_unless_exploding(position.wrapping_sub(1));
+                self.clear_tile_unless_exploding(below);
+            }
+            K_ABOUT_TO_ROLL_LEFT_STATE => {
+                self.clear_tile_unless_exploding(position.wrapping_sub(1))
+            }
+            K_ABOUT_TO_ROLL_RIGHT_STATE => self.clear_tile_unless_exploding(position + 1),
+            _ => {}
+        }
+    }
+
+    /// Plays the next frame of the explosion at the given position (updateExplosionTiles)
+    pub fn update_explosion(&mut self, position: usize) {
+        if self.tile_at(position) != LevelTileType::Explosion as u8 {
+            return;
+        }
+
+        let state = self.g_current_level_state[position].state + 1;
+        if state & K_INFOTRON_EXPLOSION_FLAG != 0 {
+            if state == K_INFOTRON_EXPLOSION_FLAG + K_INFOTRON_EXPLOSION_FRAMES {
+                self.set_tile(position, LevelTileType::Infotron, 0);
+                self.g_is_explosion_started = self.is_any_explosion_left();
+            } else {
+                self.set_state(position, state);
+            }
+            return;
+        }
+
+        self.set_state(position, state);
+        if state == K_EXPLOSION_FRAMES {
+            self.set_word(position, 0);
+            self.g_is_explosion_started = self.is_any_explosion_left();
+        }
+    }
+
+    /// The explosion flag stays set as long as an explosion is going on in the level, because
+    /// the game loop draws a random number on every frame while it's set
+    fn is_any_explosion_left(&self) -> bool {
+        (0..K_LEVEL_SIZE).any(|position| self.tile_at(position) == LevelTileType::Explosion as u8)
+    }
+
+    /// Detonates the explosive objects caught in an explosion once their timer runs out
+    /// (updateExplosionTimers)
+    pub fn update_explosion_timers(&mut self) {
+        for position in 0..K_LEVEL_SIZE {
+            let timer = self.g_explosion_timers[position] as i8;
+            if timer == 0 {
+                continue;
+            }
+
+            if timer < 0 {
+                let timer = timer + 1;
+                self.g_explosion_timers[position] = timer as u8;
+                if timer == 0 {
+                    // The explosion comes from an electron, and leaves infotrons like electrons do
+                    self.set_word(position, tile_word(LevelTileType::Electron, 0xFF));
+                    self.detonate(position);
+                }
+            } else {
+                let timer = timer - 1;
+                self.g_explosion_timers[position] = timer as u8;
+                if timer == 0 {
+                    self.detonate(position);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,401 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Gravity for zonks, infotrons and orange disks (updateZonkTiles, updateInfotronTiles and
+// updateOrangeDiskTiles). Orange disks never roll, and explode when they land on something.
+//
+// The state of a zonk or infotron is its movement phase in the high nibble and the number of
+// frames already played in the low nibble. An object moves into its destination tile as soon as
+// its movement starts, and the tile it leaves is marked with K_OBJECT_LEAVING_WORD until the end
+// of the movement:
+// - 0x10-0x17: falling from the tile above
+// - 0x20-0x27: rolling from the tile on the right
+// - 0x30-0x37: rolling from the tile on the left
+// - 0x40-0x41: about to fall
+// - 0x50-0x51: about to roll left, the destination is marked with K_ROLLING_DESTINATION_WORD
+// - 0x60-0x61: about to roll right
+// - 0x70: just landed, keeps falling if the tile below is still empty
+//
+// The state of an orange disk is 0x20-0x21 while it waits before falling, and 0x30-0x37 while it
+// falls. The tile below it is reserved with K_ORANGE_DISK_DESTINATION_STATE.
+
+use crate::game::game_states::GameStates;
+use crate::game::globals::*;
+use crate::game::simulation::{
+    tile_word, K_ENEMY_LEAVING_TILE, K_FALLING_DESTINATION_WORD, K_OBJECT_LEAVING_WORD,
+    K_ROLLING_DESTINATION_WORD, K_ROLLING_SOURCE_WORD,
+};
+use crate::game::sounds::SoundEffect;
+
+pub const K_FALLING_STATE: u8 = 0x10;
+pub const K_ROLLING_LEFT_STATE: u8 = 0x20;
+pub const K_ROLLING_RIGHT_STATE: u8 = 0x30;
+const K_ABOUT_TO_FALL_STATE: u8 = 0x40;
+const K_ABOUT_TO_ROLL_LEFT_STATE: u8 = 0x50;
+const K_ABOUT_TO_ROLL_RIGHT_STATE: u8 = 0x60;
+const K_LANDED_STATE: u8 = 0x70;
+
+// Frames played before the object leaves its tile to fall or roll
+const K_START_MOVING_FRAMES: u8 = 2;
+// The rolling movement starts 2 frames into the animation
+const K_ROLLING_START_FRAME: u8 = 2;
+// Frames at which the tile left by the object is released
+const K_ROLLING_SOURCE_FRAME: u8 = 4;
+const K_CLEAN_UP_FRAME: u8 = 6;
+const K_MOVE_FRAMES: u8 = 8;
+
+const K_ORANGE_DISK_WAITING_STATE: u8 = 0x20;
+const K_ORANGE_DISK_FALLING_STATE: u8 = 0x30;
+const K_ORANGE_DISK_WAITING_FRAMES: u8 = 2;
+// State of the empty tile an orange disk is about to fall into
+const K_ORANGE_DISK_DESTINATION_STATE: u8 = 0x08;
+
+// In the original game, zonks are frozen when this value is 2 (byte_51035)
+const K_ZONKS_FROZEN: u8 = 2;
+
+// States of Murphy pushing something sideways, when a zonk or infotron landing on him doesn't
+// kill him
+const K_MURPHY_PUSHING_STATES: [u8; 6] = [0x0E, 0x0F, 0x28, 0x29, 0x25, 0x26];
+
+// Words of an enemy leaving the tile below towards the left and the right
+const K_ENEMY_LEAVING_LEFT_WORD: u16 = 0x0200 | K_ENEMY_LEAVING_TILE as u16;
+const K_ENEMY_LEAVING_RIGHT_WORD: u16 = 0x0400 | K_ENEMY_LEAVING_TILE as u16;
+
+/// Tells if a tile lets zonks and infotrons roll off it. Only resting objects do.
+fn is_rounded_word(word: u16) -> bool {
+    word == LevelTileType::Zonk as u16
+        || word == LevelTileType::Infotron as u16
+        || word == LevelTileType::Chip as u16
+}
+
+/// Tells if a zonk or infotron can roll down into the tile with the given word
+fn is_free_to_roll_into(word: u16) -> bool {
+    word == 0 || word == K_ROLLING_DESTINATION_WORD || word == K_ROLLING_SOURCE_WORD
+}
+
+impl GameStates {
+    /// Updates the zonk at the given position (updateZonkTiles)
+    pub fn update_zonk(&mut self, position: usize) {
+        self.update_zonk_or_infotron(position, LevelTileType::Zonk);
+    }
+
+    /// Updates the infotron at the given position (updateInfotronTiles)
+    pub fn update_infotron(&mut self, position: usize) {
+        self.update_zonk_or_infotron(position, LevelTileType::Infotron);
+    }
+
+    fn are_zonks_frozen(&self, tile: LevelTileType) -> bool {
+        tile == LevelTileType::Zonk && self.g_are_zonks_frozen == K_ZONKS_FROZEN
+    }
+
+    fn update_zonk_or_infotron(&mut self, position: usize, tile: LevelTileType) {
+        if self.tile_at(position) != tile as u8 {
+            return;
+        }
+
+        // Resting objects start to move when they can
+        if self.word_at(position) == tile as u16
+            && (self.are_zonks_frozen(tile) || !self.start_zonk_or_infotron_move(position, tile))
+        {
+            return;
+        }
+
+        let state = self.g_current_level_state[position].state;
+        match state & 0xF0 {
+            K_FALLING_STATE => self.update_falling(position, tile),
+            K_ROLLING_LEFT_STATE => self.update_rolling(position, tile, true),
+            K_ROLLING_RIGHT_STATE => self.update_rolling(position, tile, false),
+            _ if self.are_zonks_frozen(tile) => {}
+            K_ABOUT_TO_FALL_STATE => {
+                let state = state + 1;
+                if state < K_ABOUT_TO_FALL_STATE + K_START_MOVING_FRAMES {
+                    self.set_state(position, state);
+                } else if self.word_at(position + K_LEVEL_WIDTH) != 0 {
+                    self.set_state(position, state - 1);
+                } else {
+                    self.start_falling(position, tile);
+                }
+            }
+            K_ABOUT_TO_ROLL_LEFT_STATE => self.update_about_to_roll(position, tile, true),
+            K_ABOUT_TO_ROLL_RIGHT_STATE => self.update_about_to_roll(position, tile, false),
+            K_LANDED_STATE => {
+                let below = self.word_at(position + K_LEVEL_WIDTH);
+                if below == 0 || below == K_FALLING_DESTINATION_WORD {
+                    self.start_falling(position, tile);
+                } else {
+                    self.set_state(position, 0);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Looks if a resting zonk or infotron has to fall or roll off what is below it. Returns true
+    /// if it starts moving.
+    fn start_zonk_or_infotron_move(&mut self, position: usize, tile: LevelTileType) -> bool {
+        let below = position + K_LEVEL_WIDTH;
+        if self.word_at(below) == 0 {
+            self.set_state(position, K_ABOUT_TO_FALL_STATE);
+            return true;
+        }
+        if !is_rounded_word(self.word_at(below)) {
+            return false;
+        }
+
+        let left = position.wrapping_sub(1);
+        if is_free_to_roll_into(self.word_at(below - 1)) && self.word_at(left) == 0 {
+            self.set_state(position, K_ABOUT_TO_ROLL_LEFT_STATE);
+            self.set_word(left, K_ROLLING_DESTINATION_WORD);
+            return true;
+        }
+
+        // Zonks can also roll right into a tile another zonk is about to fall into
+        let right = position + 1;
+        let can_roll_right = self.word_at(right) == 0
+            || (tile == LevelTileType::Zonk
+                && self.word_at(right) == K_FALLING_DESTINATION_WORD
+                && self.tile_at(right.wrapping_sub(K_LEVEL_WIDTH)) == LevelTileType::Zonk as u8);
+        if is_free_to_roll_into(self.word_at(below + 1)) && can_roll_right {
+            self.set_state(position, K_ABOUT_TO_ROLL_RIGHT_STATE);
+            self.set_word(right, K_ROLLING_DESTINATION_WORD);
+            return true;
+        }
+
+        false
+    }
+
+    /// Moves the object into the tile below, and plays the first frame of the fall
+    fn start_falling(&mut self, position: usize, tile: LevelTileType) {
+        let below = position + K_LEVEL_WIDTH;
+        self.set_word(position, K_OBJECT_LEAVING_WORD);
+        self.set_tile(below, tile, K_FALLING_STATE);
+        self.update_falling(below, tile);
+    }
+
+    fn update_falling(&mut self, position: usize, tile: LevelTileType) {
+        let state = self.g_current_level_state[position].state + 1;
+        if state == K_FALLING_STATE + K_CLEAN_UP_FRAME {
+            self.set_state(position, state);
+            self.clean_up_above_falling_object(position.wrapping_sub(K_LEVEL_WIDTH), tile);
+            return;
+        }
+        if state < K_FALLING_STATE + K_MOVE_FRAMES {
+            self.set_state(position, state);
+            return;
+        }
+
+        self.set_state(position, 0);
+        if self.are_zonks_frozen(tile) {
+            return;
+        }
+
+        let below = position + K_LEVEL_WIDTH;
+        let below_word = self.word_at(below);
+        if below_word == 0 || below_word == K_FALLING_DESTINATION_WORD {
+            // The object keeps falling on the next frame
+            self.set_state(position, K_LANDED_STATE);
+            self.set_word(below, K_FALLING_DESTINATION_WORD);
+            return;
+        }
+
+        let below_tile = (below_word & 0xFF) as u8;
+        if below_tile == LevelTileType::Murphy as u8 {
+            if !K_MURPHY_PUSHING_STATES.contains(&((below_word >> 8) as u8)) {
+                self.detonate(below);
+            }
+            return;
+        }
+        if below_tile == LevelTileType::SnikSnak as u8
+            || below_tile == LevelTileType::Electron as u8
+            || below_tile == LevelTileType::OrangeDisk as u8
+        {
+            self.detonate(below);
+            return;
+        }
+        if below_word == K_ENEMY_LEAVING_LEFT_WORD {
+            self.detonate(below - 1);
+            return;
+        }
+        if below_word == K_ENEMY_LEAVING_RIGHT_WORD {
+            self.detonate(below + 1);
+            return;
+        }
+
+        self.queue_sound_effect(SoundEffect::Fall);
+        if !is_rounded_word(below_word) {
+            return;
+        }
+
+        let left = position.wrapping_sub(1);
+        if is_free_to_roll_into(self.word_at(below - 1)) && self.word_at(left) == 0 {
+            self.set_state(position, K_ABOUT_TO_ROLL_LEFT_STATE);
+            self.set_word(left, K_ROLLING_DESTINATION_WORD);
+            return;
+        }
+        let right = position + 1;
+        if is_free_to_roll_into(self.word_at(below + 1)) && self.word_at(right) == 0 {
+            self.set_state(position, K_ABOUT_TO_ROLL_RIGHT_STATE);
+            self.set_word(right, K_ROLLING_DESTINATION_WORD);
+        }
+    }
+
+    fn update_about_to_roll(&mut self, position: usize, tile: LevelTileType, is_left: bool) {
+        let phase = if is_left {
+            K_ABOUT_TO_ROLL_LEFT_STATE
+        } else {
+            K_ABOUT_TO_ROLL_RIGHT_STATE
+        };
+        let state = self.g_current_level_state[position].state + 1;
+        if state < phase + K_START_MOVING_FRAMES {
+            self.set_state(position, state);
+            return;
+        }
+
+        let side = if is_left {
+            position.wrapping_sub(1)
+        } else {
+            position + 1
+        };
+        let side_word = self.word_at(side);
+        if self.word_at(side + K_LEVEL_WIDTH) != 0
+            || (side_word != 0 && side_word != K_ROLLING_DESTINATION_WORD)
+        {
+            self.set_state(position, state - 1);
+            return;
+        }
+
+        let rolling_state = if is_left {
+            K_ROLLING_LEFT_STATE
+        } else {
+            K_ROLLING_RIGHT_STATE
+        };
+        self.set_word(position, K_OBJECT_LEAVING_WORD);
+        self.set_tile(side, tile, rolling_state + K_ROLLING_START_FRAME);
+        self.set_word(side + K_LEVEL_WIDTH, K_OBJECT_LEAVING_WORD);
+        self.update_rolling(side, tile, is_left);
+    }
+
+    fn update_rolling(&mut self, position: usize, tile: LevelTileType, is_left: bool) {
+        let (phase, source) = if is_left {
+            (K_ROLLING_LEFT_STATE, position + 1)
+        } else {
+            (K_ROLLING_RIGHT_STATE, position.wrapping_sub(1))
+        };
+        let state = self.g_current_level_state[position].state + 1;
+        if state == phase + K_ROLLING_SOURCE_FRAME {
+            self.set_word(source, K_ROLLING_SOURCE_WORD);
+        }
+        if state == phase + K_CLEAN_UP_FRAME {
+            self.set_state(position, state);
+            self.clean_up_above_falling_object(source, tile);
+            return;
+        }
+        if state < phase + K_MOVE_FRAMES {
+            self.set_state(position, state);
+            return;
+        }
+
+        // After rolling, the object falls into the tile below, which was reserved for it
+        self.set_word(position, K_OBJECT_LEAVING_WORD);
+        self.set_tile(position + K_LEVEL_WIDTH, tile, K_FALLING_STATE);
+    }
+
+    /// Frees the tile a zonk or infotron left, and lets the objects above it fall or roll into it
+    /// (subCleanUpForZonksAbove and subCleanUpForInfotronsAbove)
+    fn clean_up_above_falling_object(&mut self, position: usize, tile: LevelTileType) {
+        self.clear_tile_unless_exploding(position);
+
+        // The original game checks the other kind of object two tiles above
+        let other_tile = if tile == LevelTileType::Zonk {
+            LevelTileType::Infotron
+        } else {
+            LevelTileType::Zonk
+        };
+        let above = position.wrapping_sub(K_LEVEL_WIDTH);
+        let above_word = self.word_at(above);
+        if above_word != 0
+            && (above_word != K_FALLING_DESTINATION_WORD
+                || self.tile_at(above.wrapping_sub(K_LEVEL_WIDTH)) != other_tile as u8)
+        {
+            return;
+        }
+
+        if self.word_at(above.wrapping_sub(1)) == tile as u16
+            && is_rounded_word(self.word_at(position.wrapping_sub(1)))
+        {
+            self.set_word(
+                above.wrapping_sub(1),
+                tile_word(tile, K_ABOUT_TO_ROLL_RIGHT_STATE),
+            );
+            self.set_word(above, K_ROLLING_DESTINATION_WORD);
+            return;
+        }
+        if self.word_at(above + 1) == tile as u16 && is_rounded_word(self.word_at(position + 1)) {
+            self.set_word(above + 1, tile_word(tile, K_ABOUT_TO_ROLL_LEFT_STATE));
+            self.set_word(above, K_ROLLING_DESTINATION_WORD);
+        }
+    }
+
+    /// Updates the orange disk at the given position (updateOrangeDiskTiles)
+    pub fn update_orange_disk(&mut self, position: usize) {
+        if self.tile_at(position) != LevelTileType::OrangeDisk as u8 {
+            return;
+        }
+
+        let state = self.g_current_level_state[position].state;
+        let below = position + K_LEVEL_WIDTH;
+        if state >= K_ORANGE_DISK_FALLING_STATE {
+            self.update_falling_orange_disk(position);
+        } else if state >= K_ORANGE_DISK_WAITING_STATE {
+            if self.word_at(below) == 0 {
+                self.set_state(position, 0);
+                return;
+            }
+            let state = state + 1;
+            if state == K_ORANGE_DISK_WAITING_STATE + K_ORANGE_DISK_WAITING_FRAMES {
+                self.set_state(position, K_ORANGE_DISK_FALLING_STATE);
+            } else {
+                self.set_state(position, state);
+            }
+        } else if self.word_at(below) == 0 {
+            self.set_state(position, K_ORANGE_DISK_WAITING_STATE);
+            self.set_state(below, K_ORANGE_DISK_DESTINATION_STATE);
+        }
+    }
+
+    fn update_falling_orange_disk(&mut self, position: usize) {
+        let state = self.g_current_level_state[position].state + 1;
+        if state & 0x07 != 0 {
+            self.set_state(position, state);
+            return;
+        }
+
+        let position = position + K_LEVEL_WIDTH;
+        self.set_word(position - K_LEVEL_WIDTH, 0);
+        self.set_tile(position, LevelTileType::OrangeDisk, 0);
+
+        let below = position + K_LEVEL_WIDTH;
+        if self.word_at(below) == 0 {
+            self.set_state(position, K_ORANGE_DISK_FALLING_STATE);
+            self.set_state(below, K_ORANGE_DISK_DESTINATION_STATE);
+            return;
+        }
+        if self.tile_at(below) != LevelTileType::Explosion as u8 {
+            self.detonate(position);
+        }
+    }
+}
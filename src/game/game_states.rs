@@ -1,26 +1,30 @@
 use crate::game::globals::*;
 
 use crate::game::animation::MurphyAnimationDescriptor;
+use crate::game::keyboard::UserInput;
 use crate::game::level::Level;
+use crate::game::sounds::SoundEffect;
 
 pub struct GameStates {
     pub g_current_level_state_with_padding:
         Box<[StatefulLevelTile; K_LEVEL_DATA_LENGTH + K_SIZE_OF_LEVEL_STATE_PRECEDING_PADDING]>, // 0x1584
     pub g_current_level_state: Box<[StatefulLevelTile; K_LEVEL_DATA_LENGTH]>, // located at 0x1834, size is kLevelDataLength items
     pub g_explosion_timers: Box<[u8; K_LEVEL_DATA_LENGTH]>,                   // 0x2434
+    pub g_terminal_scroll_offsets: Box<[u8; K_LEVEL_DATA_LENGTH]>, // Lines scrolled by each terminal screen
     pub g_is_gravity_enabled: u8, // byte_5101C -> 1 = turn on, anything else (0) = turn off
     pub g_are_zonks_frozen: u8, // byte_51035 -> 2 = turn on, anything else (0) = turn off  (1=off!)
     pub g_number_of_info_trons: u8, // 0xd26 -> byte_51036 -> this seems to be _inside_ of fileLevelData when a level is read
     pub g_number_of_special_ports: u8, // 0xd27 -> byte_51037 this seems to be _inside_ of fileLevelData when a level is read, and it's numberOfSpecialPorts
     pub g_random_seed: u16,            // word_51076
-    pub g_aux_game_seconds_20ms_accumulator: u8, // byte_510AF ->  -> accumulates game time. The total time is its value * 20ms, so when it reaches 50 it means 1 second. Used to increase the game time in the bottom panel
+    pub g_random_generator_seed: u16,
+    pub g_aux_game_seconds_20ms_accumulator: u8, // byte_510AF ->  -> accumulates game time. In the original game the total time is its value * 20ms. Here it counts frames, so when it reaches 35 it means 1 second. Used to increase the game time in the bottom panel
     pub g_game_seconds: u8,                      // byte_510B0
     pub g_game_minutes: u8,                      // byte_510B1
     pub g_game_hours: u8,                        // byte_510B2
     pub g_should_update_total_level_time: bool,  // byte_510B3
     pub g_level_failed: bool,                    // byte_510BA
     pub g_current_player_level_state: PlayerLevelState, // byte_510BB
-    pub g_is_explosion_started: bool, // byte_510C0 -> Set to 1 when an explosion is just created. Set back to 0 when _any_ of the explosions on the screen disappears.
+    pub g_is_explosion_started: bool, // byte_510C0 -> Set to 1 when an explosion is just created. Set back to 0 when the last explosion on the screen disappears.
     pub g_should_show_game_panel: bool, // byte_510C1 -> 0DB1
     pub g_toggle_game_panel_key_auto_repeat_counter: u8, // byte_510C2 -> 0DB2
     pub g_murphy_tile_x: usize,       // word_510C3
@@ -59,10 +63,13 @@ pub struct GameStates {
     pub g_quit_level_countdown: u16, // word_51978 -> this is a counter to end the level after certain number of iterations (to let the game progress a bit before going back to the menu)
     pub g_additional_info_in_game_panel_frame_counter: u8, // byte_5197C -> how many more frames the additional info in the game panel will be
     pub g_current_level: Level,                            // 0x988B
+    pub g_current_user_input: UserInput,
+    pub g_pending_sound_effects: Vec<SoundEffect>,
+    pub g_murphy_animation_anchor_location: usize, // Tile the Murphy animation coordinates offset is relative to
     pub g_is_playing_demo: bool,
     pub g_current_player_index: usize,
-    pub g_current_player_padded_level_data: Box<[u8; K_NUMBER_OF_LEVEL_WITH_PADDING]>,
-    pub g_current_selected_level_index: u8,
+    pub g_current_player_padded_level_data: Vec<u8>, // Color of each entry of the list of levels
+    pub g_current_selected_level_index: u16,
     pub g_player_name: String,
     pub g_current_level_name: String,
     pub g_is_level_started_as_demo: bool,
@@ -85,11 +92,13 @@ impl GameStates {
                 K_LEVEL_DATA_LENGTH + K_SIZE_OF_LEVEL_STATE_PRECEDING_PADDING]),
             g_current_level_state: Box::new([StatefulLevelTile::default(); K_LEVEL_DATA_LENGTH]),
             g_explosion_timers: Box::new([0; K_LEVEL_DATA_LENGTH]),
+            g_terminal_scroll_offsets: Box::new([0; K_LEVEL_DATA_LENGTH]),
             g_is_gravity_enabled: 0,
             g_are_zonks_frozen: 0,
             g_number_of_info_trons: 0,
             g_number_of_special_ports: 0,
             g_random_seed: 0,
+            g_random_generator_seed: 0,
             g_aux_game_seconds_20ms_accumulator: 0,
             g_game_seconds: 0,
             g_game_minutes: 0,
@@ -136,10 +145,13 @@ impl GameStates {
             g_quit_level_countdown: 0,
             g_additional_info_in_game_panel_frame_counter: 0,
             g_current_level: Level::new(),
+            g_current_user_input: UserInput::None,
+            g_pending_sound_effects: Vec::new(),
+            g_murphy_animation_anchor_location: 0,
             g_is_playing_demo: false,
             g_current_player_index: 0,
 
-            g_current_player_padded_level_data: Box::new([0; K_NUMBER_OF_LEVEL_WITH_PADDING]),
+            g_current_player_padded_level_data: vec![0; K_NUMBER_OF_LEVELS + K_LEVEL_LIST_PADDING],
             g_current_selected_level_index: 1,
             g_player_name: String::new(),
             g_current_level_name: String::new(),
@@ -29,10 +29,9 @@ pub const K_LEVEL_WIDTH: usize = 60;
 pub const K_LEVEL_HEIGHT: usize = 24;
 pub const K_LEVEL_SIZE: usize = K_LEVEL_WIDTH * K_LEVEL_HEIGHT;
 
-pub const K_NUMBER_OF_LEVELS: usize = 111;
-pub const K_NUMBER_OF_LEVEL_WITH_PADDING: usize = K_NUMBER_OF_LEVELS + 5;
+pub const K_NUMBER_OF_LEVELS: usize = 111; // Number of levels of the original level sets, other sets can have more or less
+pub const K_LEVEL_LIST_PADDING: usize = 5; // Entries of the list of levels that are not levels
 pub const K_FIRST_LEVEL_INDEX: usize = 2;
-pub const K_LAST_LEVEL_INDEX: usize = K_FIRST_LEVEL_INDEX + K_NUMBER_OF_LEVELS;
 pub const K_LIST_LEVEL_NAME_LENGTH: usize = 28; // In the list of levels, every level is 28 bytes long and looks like "001
 pub const K_LEVEL_NAME_LENGTH: usize = 24;
 
@@ -49,6 +48,7 @@ pub const G_LEVELS_LST_FILENAME: &str = "LEVEL.LST";
 pub const G_PLAYERS_LST_FILENAME: &str = "PLAYER.LST";
 pub const G_HALL_OF_FAME_LST_FILENAME: &str = "HALLFAME.LST";
 pub const G_SAVEGAME_SAV_FILENAME: &str = "SAVEGAME.SAV";
+pub const G_DEMO_SIGNATURE_FILENAME: &str = "myspsig.txt";
 
 // Graphic file names
 pub const G_MENU_DAT_FILENAME: &str = "MENU.DAT";
@@ -148,22 +148,22 @@ pub struct PlayerEntry {
     pub hours: u8,
     pub minutes: u8,
     pub seconds: u8,
-    pub level_state: [PlayerLevelState; K_NUMBER_OF_LEVELS], // values are PlayerLevelState
+    pub level_state: Vec<PlayerLevelState>, // One state per level of the level set
     pub unknown1: u8,
     pub unknown2: u8,
     pub unknown3: u8,
-    pub next_level_to_play: u8,
+    pub next_level_to_play: u16,
     pub completed_all_levels: u8, // Still not 100% sure
 }
 
 impl PlayerEntry {
-    pub fn new() -> PlayerEntry {
+    pub fn new(number_of_levels: usize) -> PlayerEntry {
         PlayerEntry {
             name: String::from("--------"), // Default player name
             hours: 0,
             minutes: 0,
             seconds: 0,
-            level_state: [PlayerLevelState::NotCompleted; K_NUMBER_OF_LEVELS], // values are PlayerLevelState
+            level_state: vec![PlayerLevelState::NotCompleted; number_of_levels],
             unknown1: 0,
             unknown2: 0,
             unknown3: 0,
@@ -172,22 +172,27 @@ impl PlayerEntry {
         }
     }
 
-    pub fn from(player_data: [u8; K_PLAYER_ENTRY_SIZE]) -> PlayerEntry {
+    /// Reads a player entry of player_entry_size(number_of_levels) bytes
+    pub fn from(player_data: &[u8], number_of_levels: usize) -> PlayerEntry {
+        let trailer_offset = K_PLAYER_NAME_LENGTH + number_of_levels;
         let mut pe = PlayerEntry {
             name: String::from_utf8_lossy(&player_data[0..K_PLAYER_NAME_LENGTH]).to_string(), // Default player name
             hours: player_data[K_PLAYER_NAME_LENGTH + 1],
             minutes: player_data[K_PLAYER_NAME_LENGTH + 2],
             seconds: player_data[K_PLAYER_NAME_LENGTH + 3],
-            level_state: [PlayerLevelState::NotCompleted; K_NUMBER_OF_LEVELS], // values are PlayerLevelState
+            level_state: vec![PlayerLevelState::NotCompleted; number_of_levels],
 
-            unknown1: player_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 4],
-            unknown2: player_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 5],
-            unknown3: player_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 6],
-            next_level_to_play: player_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 7],
-            completed_all_levels: player_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 8], // Still not 100% sure
+            unknown1: player_data[trailer_offset + 4],
+            unknown2: player_data[trailer_offset + 5],
+            unknown3: player_data[trailer_offset + 6],
+            next_level_to_play: player_data[trailer_offset + 7] as u16,
+            completed_all_levels: player_data[trailer_offset + 8], // Still not 100% sure
         };
+        if number_of_levels != K_NUMBER_OF_LEVELS {
+            pe.next_level_to_play |= (player_data[trailer_offset + 9] as u16) << 8;
+        }
 
-        for j in 0..K_NUMBER_OF_LEVELS {
+        for j in 0..number_of_levels {
             pe.level_state[j] = player_data[K_PLAYER_NAME_LENGTH + 4 + j]
                 .try_into()
                 .unwrap();
@@ -196,8 +201,9 @@ impl PlayerEntry {
         pe
     }
 
-    pub fn to_raw(&self) -> [u8; K_PLAYER_ENTRY_SIZE] {
-        let mut raw_data = [0_u8; K_PLAYER_ENTRY_SIZE];
+    pub fn to_raw(&self) -> Vec<u8> {
+        let number_of_levels = self.level_state.len();
+        let mut raw_data = vec![0_u8; player_entry_size(number_of_levels)];
 
         let name = self.name.as_bytes();
         for i in 0..name.len() {
@@ -210,14 +216,18 @@ impl PlayerEntry {
         raw_data[K_PLAYER_NAME_LENGTH + 1] = self.hours;
         raw_data[K_PLAYER_NAME_LENGTH + 2] = self.minutes;
         raw_data[K_PLAYER_NAME_LENGTH + 3] = self.seconds;
-        for i in 0..K_NUMBER_OF_LEVELS {
+        for i in 0..number_of_levels {
             raw_data[K_PLAYER_NAME_LENGTH + 4 + i] = self.level_state[i] as u8;
         }
-        raw_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 4] = self.unknown1;
-        raw_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 5] = self.unknown2;
-        raw_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 6] = self.unknown3;
-        raw_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 7] = self.next_level_to_play;
-        raw_data[K_PLAYER_NAME_LENGTH + K_NUMBER_OF_LEVELS + 8] = self.completed_all_levels;
+        let trailer_offset = K_PLAYER_NAME_LENGTH + number_of_levels;
+        raw_data[trailer_offset + 4] = self.unknown1;
+        raw_data[trailer_offset + 5] = self.unknown2;
+        raw_data[trailer_offset + 6] = self.unknown3;
+        raw_data[trailer_offset + 7] = self.next_level_to_play as u8;
+        raw_data[trailer_offset + 8] = self.completed_all_levels;
+        if number_of_levels != K_NUMBER_OF_LEVELS {
+            raw_data[trailer_offset + 9] = (self.next_level_to_play >> 8) as u8;
+        }
         raw_data
     }
 }
@@ -266,12 +276,23 @@ impl HallOfFameEntry {
     }
 }
 
-pub const K_PLAYER_ENTRY_SIZE: usize = K_PLAYER_NAME_LENGTH + 1 + 3 + K_NUMBER_OF_LEVELS + 5;
 pub const K_HALL_OF_FAME_ENTRY_SIZE: usize = K_PLAYER_NAME_LENGTH + 1 + 3;
 pub const K_PLAYER_NAME_LENGTH: usize = 8;
 pub const K_NUMBER_OF_PLAYERS: usize = 20;
 pub const K_NUMBER_OF_HALL_OF_FAME_ENTRIES: usize = 3;
 
+/// Size of the player entries for a level set with the given number of levels. Level sets other
+/// than the original ones can have more than 255 levels, so the high byte of the next level to
+/// play is added at the end of their entries.
+pub const fn player_entry_size(number_of_levels: usize) -> usize {
+    let size = K_PLAYER_NAME_LENGTH + 1 + 3 + number_of_levels + 5;
+    if number_of_levels == K_NUMBER_OF_LEVELS {
+        size
+    } else {
+        size + 1
+    }
+}
+
 #[derive(Default, Clone, Copy)]
 pub struct SpecialPortInfo {
     // If (x,y) are the coordinates of a port in the field
@@ -279,6 +300,7 @@ pub struct SpecialPortInfo {
     // here calculates as 2*(x+(y*60)).  This is twice of
     // what you may have expected: Supaplex works with a
     // game field in memory, which is 2 bytes per sprite.
+    // It's stored high byte first.
     pub position: u16,
 
     pub gravity: u8,        // 1 = turn on, anything else (0) = turn off
@@ -287,23 +309,6 @@ pub struct SpecialPortInfo {
     pub unused: u8,         // Doesn't matter: is ignored.
 }
 
-impl SpecialPortInfo {
-    pub fn from_raw(raw_data: &[u8]) -> SpecialPortInfo {
-        if raw_data.len() != K_SPECIAL_PORT_STRUCT_SIZE {
-            panic!("Wrong input size !!!");
-        }
-        SpecialPortInfo {
-            position: ((raw_data[0] as u16) << 8) + raw_data[1] as u16, // LE or BE ?
-            gravity: raw_data[2],
-            freeze_zonks: raw_data[3],
-            freeze_enemies: raw_data[4],
-            unused: raw_data[5],
-        }
-    }
-}
-
-pub const K_SPECIAL_PORT_STRUCT_SIZE: usize = 6;
-
 pub const K_LEVEL_MAX_NUMBER_OF_SPECIAL_PORTS: usize = 10;
 
 #[derive(PartialEq)]
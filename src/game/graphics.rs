@@ -15,8 +15,29 @@
 * along with this program. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use crate::game::animation::{
+    K_BUG_FRAME_COORDINATES, K_ELECTRON_ANIMATION_FRAME_COORDINATES,
+    K_INFOTRON_EXPLOSION_ANIMATION_FRAME_COORDINATES,
+    K_INFOTRON_SLIDE_LEFT_ANIMATION_FRAME_COORDINATES,
+    K_INFOTRON_SLIDE_RIGHT_ANIMATION_FRAME_COORDINATES, K_MURPHY_ANIMATION_FRAME_COORDINATES,
+    K_REGULAR_EXPLOSION_ANIMATION_FRAME_COORDINATES, K_SNIK_SNAK_ANIMATION_FRAME_COORDINATES,
+    K_ZONK_SLIDE_LEFT_ANIMATION_FRAME_COORDINATES, K_ZONK_SLIDE_RIGHT_ANIMATION_FRAME_COORDINATES,
+};
+use crate::game::bugs::is_bug_active;
 use crate::game::button_borders::{ButtonBorderLineDescriptor, ButtonBorderLineType};
+use crate::game::enemies::{
+    K_ENEMY_MOVING_DOWN_STATE, K_ENEMY_MOVING_LEFT_STATE, K_ENEMY_MOVING_RIGHT_STATE,
+    K_ENEMY_MOVING_UP_STATE,
+};
+use crate::game::explosions::K_INFOTRON_EXPLOSION_FLAG;
+use crate::game::falling_objects::{K_FALLING_STATE, K_ROLLING_LEFT_STATE, K_ROLLING_RIGHT_STATE};
+use crate::game::game_states::GameStates;
 use crate::game::globals::*;
+use crate::game::murphy::{
+    K_MURPHY_STATE_PASSING_PORT, K_MURPHY_STATE_SNAPPING_BASE, K_MURPHY_STATE_SNAPPING_INFOTRON,
+    K_MURPHY_STATE_SNAPPING_RED_DISK,
+};
+use crate::game::simulation::is_moving_tile;
 use crate::game::video::Video;
 use sdl2::pixels::{Color, Palette};
 use std::cell::RefCell;
@@ -211,13 +232,16 @@ impl Graphics<'_> {
                     let source_pixel_address = y * K_FIXED_BITMAP_WIDTH / 2 + x / 8;
                     let source_pixel_bit_position = 7 - (x % 8);
 
+                    // Each line has 4 planes of K_FIXED_BITMAP_WIDTH / 8 bytes
                     let b: u8 =
                         (bitmap_data[source_pixel_address + 0] >> source_pixel_bit_position) & 0x1;
-                    let g: u8 =
-                        (bitmap_data[source_pixel_address + 40] >> source_pixel_bit_position) & 0x1;
-                    let r: u8 =
-                        (bitmap_data[source_pixel_address + 80] >> source_pixel_bit_position) & 0x1;
-                    let i: u8 = (bitmap_data[source_pixel_address + 120]
+                    let g: u8 = (bitmap_data[source_pixel_address + K_FIXED_BITMAP_WIDTH / 8]
+                        >> source_pixel_bit_position)
+                        & 0x1;
+                    let r: u8 = (bitmap_data[source_pixel_address + K_FIXED_BITMAP_WIDTH / 4]
+                        >> source_pixel_bit_position)
+                        & 0x1;
+                    let i: u8 = (bitmap_data[source_pixel_address + K_FIXED_BITMAP_WIDTH * 3 / 8]
                         >> source_pixel_bit_position)
                         & 0x1;
 
@@ -1159,41 +1183,357 @@ impl Graphics<'_> {
         }
     }
 
-    fn draw_moving_sprite_frame_in_level(
+    pub fn draw_moving_sprite_frame_in_level(
         &mut self,
         src_x: usize,
         src_y: usize,
         width: usize,
         height: usize,
-        dst_x: usize,
-        dst_y: usize,
+        dst_x: i32,
+        dst_y: i32,
     ) {
         if self.fast_mode == FastModeType::Ultra {
             return;
         }
 
-        assert!((width % K_TILE_SIZE) == 0);
+        assert!((width % 8) == 0);
 
         for y in 0..height {
-            let final_y = (dst_y + y) as i32 - K_LEVEL_EDGE_SIZE as i32;
+            let final_y = dst_y + y as i32 - K_LEVEL_EDGE_SIZE as i32;
 
-            if final_y < 0 || final_y >= K_LEVEL_BITMAP_HEIGHT.try_into().unwrap() {
+            if final_y < 0 || final_y >= K_LEVEL_BITMAP_HEIGHT as i32 {
                 continue;
             }
 
-            let final_x = dst_x as i32 - K_LEVEL_EDGE_SIZE as i32;
-            let final_width =
-                std::cmp::min(width as i32, K_LEVEL_BITMAP_WIDTH as i32 - final_x) as usize;
             let src_address = (src_y + y) * K_MOVING_BITMAP_WIDTH + src_x;
-            let dst_address = (final_y * K_LEVEL_BITMAP_WIDTH as i32 + final_x) as usize;
 
-            for i in 0..K_PANEL_BITMAP_WIDTH {
-                let color = self.get_pixel_from_bitmap(BitmapType::MovingDecoded, src_address + i);
-                self.set_pixel(DestinationSurface::Level, dst_address + i, color);
+            for x in 0..width {
+                let final_x = dst_x + x as i32 - K_LEVEL_EDGE_SIZE as i32;
+
+                if final_x < 0 || final_x >= K_LEVEL_BITMAP_WIDTH as i32 {
+                    continue;
+                }
+
+                let dst_address = (final_y * K_LEVEL_BITMAP_WIDTH as i32 + final_x) as usize;
+                let color = self.get_pixel_from_bitmap(BitmapType::MovingDecoded, src_address + x);
+                self.set_pixel(DestinationSurface::Level, dst_address, color);
             }
         }
     }
 
+    /// Draws a tile from FIXED.DAT on the level bitmap at the given level position
+    pub fn draw_fixed_tile(&mut self, tile: u8, position: usize) {
+        if self.fast_mode == FastModeType::Ultra {
+            return;
+        }
+
+        let tile_x = position % K_LEVEL_WIDTH;
+        let tile_y = position / K_LEVEL_WIDTH;
+
+        // The tiles of the border are covered by the level edges
+        if tile_x == 0 || tile_y == 0 || tile_x >= K_LEVEL_WIDTH - 1 || tile_y >= K_LEVEL_HEIGHT - 1
+        {
+            return;
+        }
+
+        self.draw_fixed_tile_in_level(
+            tile,
+            (tile_x * K_TILE_SIZE) as i32,
+            (tile_y * K_TILE_SIZE) as i32,
+        );
+    }
+
+    /// Draws a tile from FIXED.DAT on the level bitmap at the given coordinates, which can be in
+    /// between two tiles for moving objects
+    pub fn draw_fixed_tile_in_level(&mut self, tile: u8, dst_x: i32, dst_y: i32) {
+        if self.fast_mode == FastModeType::Ultra {
+            return;
+        }
+
+        // Tile values greater than the official ones (including 40, the invisible wall) will be rendered as empty
+        // spaces, to prevent issues even with custom graphics.
+        //
+        let mut tile_value = tile;
+        if tile_value >= LevelTileType::Count as u8 {
+            tile_value = LevelTileType::Space as u8;
+        }
+
+        let start_src_x = tile_value as usize * K_TILE_SIZE;
+
+        for y in 0..K_TILE_SIZE {
+            let final_y = dst_y + y as i32 - K_LEVEL_EDGE_SIZE as i32;
+
+            if final_y < 0 || final_y >= K_LEVEL_BITMAP_HEIGHT as i32 {
+                continue;
+            }
+
+            for x in 0..K_TILE_SIZE {
+                let final_x = dst_x + x as i32 - K_LEVEL_EDGE_SIZE as i32;
+
+                if final_x < 0 || final_x >= K_LEVEL_BITMAP_WIDTH as i32 {
+                    continue;
+                }
+
+                let dst_address = (final_y * K_LEVEL_BITMAP_WIDTH as i32 + final_x) as usize;
+                let src_address = y * K_FIXED_BITMAP_WIDTH + start_src_x + x;
+                let color = self.get_pixel_from_bitmap(BitmapType::FixedDecoded, src_address);
+                self.set_pixel(DestinationSurface::Level, dst_address, color);
+            }
+        }
+    }
+
+    /// Redraws every tile of the level from the current level state
+    pub fn draw_level_state(&mut self, states: &GameStates) {
+        if self.fast_mode == FastModeType::Ultra {
+            return;
+        }
+
+        // Tiles with a state are moving: they are drawn by their animation frames instead
+        for tile_y in 1..(K_LEVEL_HEIGHT - 1) {
+            for tile_x in 1..(K_LEVEL_WIDTH - 1) {
+                let position = tile_y * K_LEVEL_WIDTH + tile_x;
+                let tile = states.g_current_level_state[position];
+                if tile.tile == LevelTileType::Terminal as u8 {
+                    self.draw_fixed_tile(tile.tile, position);
+                    self.draw_terminal_screen(position, states.g_terminal_scroll_offsets[position]);
+                } else if tile.state == 0 || !is_moving_tile(tile.tile) {
+                    self.draw_fixed_tile(tile.tile, position);
+                } else {
+                    self.draw_fixed_tile(LevelTileType::Space as u8, position);
+                }
+            }
+        }
+
+        for position in 0..K_LEVEL_SIZE {
+            let tile = states.g_current_level_state[position];
+            // Objects pushed by Murphy are drawn by his animation
+            if tile.state >= K_FALLING_STATE
+                && (tile.tile == LevelTileType::Zonk as u8
+                    || tile.tile == LevelTileType::Infotron as u8
+                    || tile.tile == LevelTileType::OrangeDisk as u8)
+            {
+                self.draw_falling_object(tile, position);
+            } else if tile.tile == LevelTileType::SnikSnak as u8
+                || tile.tile == LevelTileType::Electron as u8
+            {
+                self.draw_enemy(tile, position);
+            } else if tile.tile == LevelTileType::Bug as u8 && is_bug_active(tile.state) {
+                let frame = &K_BUG_FRAME_COORDINATES[tile.state as usize];
+                self.draw_moving_sprite_frame_in_level(
+                    frame.x as usize,
+                    frame.y as usize,
+                    K_TILE_SIZE,
+                    K_TILE_SIZE,
+                    ((position % K_LEVEL_WIDTH) * K_TILE_SIZE) as i32,
+                    ((position / K_LEVEL_WIDTH) * K_TILE_SIZE) as i32,
+                );
+            } else if tile.tile == LevelTileType::Explosion as u8 {
+                let frames = if tile.state & K_INFOTRON_EXPLOSION_FLAG != 0 {
+                    &K_INFOTRON_EXPLOSION_ANIMATION_FRAME_COORDINATES
+                } else {
+                    &K_REGULAR_EXPLOSION_ANIMATION_FRAME_COORDINATES
+                };
+                let frame = &frames[(tile.state & 0x07) as usize];
+                self.draw_moving_sprite_frame_in_level(
+                    frame.x as usize,
+                    frame.y as usize,
+                    K_TILE_SIZE,
+                    K_TILE_SIZE,
+                    ((position % K_LEVEL_WIDTH) * K_TILE_SIZE) as i32,
+                    ((position / K_LEVEL_WIDTH) * K_TILE_SIZE) as i32,
+                );
+            }
+        }
+
+        if states.g_planted_red_disk_countdown != 0 {
+            let position = states.g_planted_red_disk_position as usize;
+            if states.g_current_level_state[position].tile == LevelTileType::Space as u8 {
+                self.draw_fixed_tile(LevelTileType::RedDisk as u8, position);
+            }
+        }
+
+        self.draw_murphy(states);
+    }
+
+    /// Redraws the screen of a terminal scrolled by the given number of lines
+    fn draw_terminal_screen(&mut self, position: usize, scroll_offset: u8) {
+        let scroll_offset = scroll_offset as usize % K_TERMINAL_SCREEN_HEIGHT;
+        if scroll_offset == 0 {
+            return;
+        }
+
+        let start_src_x = LevelTileType::Terminal as usize * K_TILE_SIZE + K_TERMINAL_SCREEN_X;
+        let dst_x =
+            (position % K_LEVEL_WIDTH) * K_TILE_SIZE + K_TERMINAL_SCREEN_X - K_LEVEL_EDGE_SIZE;
+        let dst_y =
+            (position / K_LEVEL_WIDTH) * K_TILE_SIZE + K_TERMINAL_SCREEN_Y - K_LEVEL_EDGE_SIZE;
+
+        for y in 0..K_TERMINAL_SCREEN_HEIGHT {
+            let src_y = K_TERMINAL_SCREEN_Y + (y + scroll_offset) % K_TERMINAL_SCREEN_HEIGHT;
+            for x in 0..K_TERMINAL_SCREEN_WIDTH {
+                let src_address = src_y * K_FIXED_BITMAP_WIDTH + start_src_x + x;
+                let dst_address = (dst_y + y) * K_LEVEL_BITMAP_WIDTH + dst_x + x;
+                let color = self.get_pixel_from_bitmap(BitmapType::FixedDecoded, src_address);
+                self.set_pixel(DestinationSurface::Level, dst_address, color);
+            }
+        }
+    }
+
+    fn draw_enemy(&mut self, tile: StatefulLevelTile, position: usize) {
+        let frames = if tile.tile == LevelTileType::SnikSnak as u8 {
+            &K_SNIK_SNAK_ANIMATION_FRAME_COORDINATES
+        } else {
+            &K_ELECTRON_ANIMATION_FRAME_COORDINATES
+        };
+        let Some(frame) = frames.get(tile.state as usize) else {
+            return;
+        };
+
+        let mut dst_x = ((position % K_LEVEL_WIDTH) * K_TILE_SIZE) as i32;
+        let mut dst_y = ((position / K_LEVEL_WIDTH) * K_TILE_SIZE) as i32;
+        let mut width = K_TILE_SIZE;
+
+        // Vertical frames are drawn in between the two tiles, horizontal ones cover both tiles
+        let remaining_offset = (K_TILE_SIZE - (tile.state & 0x07) as usize * 2) as i32;
+        match tile.state & 0xF8 {
+            K_ENEMY_MOVING_UP_STATE => dst_y += remaining_offset,
+            K_ENEMY_MOVING_DOWN_STATE => dst_y -= remaining_offset,
+            K_ENEMY_MOVING_LEFT_STATE => width = K_TILE_SIZE * 2,
+            K_ENEMY_MOVING_RIGHT_STATE => {
+                width = K_TILE_SIZE * 2;
+                dst_x -= K_TILE_SIZE as i32;
+            }
+            _ => {}
+        }
+
+        self.draw_moving_sprite_frame_in_level(
+            frame.x as usize,
+            frame.y as usize,
+            width,
+            K_TILE_SIZE,
+            dst_x,
+            dst_y,
+        );
+    }
+
+    fn draw_falling_object(&mut self, tile: StatefulLevelTile, position: usize) {
+        let dst_x = ((position % K_LEVEL_WIDTH) * K_TILE_SIZE) as i32;
+        let dst_y = ((position / K_LEVEL_WIDTH) * K_TILE_SIZE) as i32;
+        let frame = (tile.state & 0x0F) as usize;
+        let is_zonk = tile.tile == LevelTileType::Zonk as u8;
+
+        match tile.state & 0xF0 {
+            K_FALLING_STATE => {
+                let offset_y = (K_TILE_SIZE - frame * 2) as i32;
+                self.draw_fixed_tile_in_level(tile.tile, dst_x, dst_y - offset_y);
+            }
+            K_ROLLING_LEFT_STATE => {
+                let frames = if is_zonk {
+                    &K_ZONK_SLIDE_LEFT_ANIMATION_FRAME_COORDINATES
+                } else {
+                    &K_INFOTRON_SLIDE_LEFT_ANIMATION_FRAME_COORDINATES
+                };
+                let frame = &frames[frame];
+                self.draw_moving_sprite_frame_in_level(
+                    frame.x as usize,
+                    frame.y as usize,
+                    K_TILE_SIZE * 2,
+                    K_TILE_SIZE,
+                    dst_x,
+                    dst_y,
+                );
+            }
+            K_ROLLING_RIGHT_STATE => {
+                let frames = if is_zonk {
+                    &K_ZONK_SLIDE_RIGHT_ANIMATION_FRAME_COORDINATES
+                } else {
+                    &K_INFOTRON_SLIDE_RIGHT_ANIMATION_FRAME_COORDINATES
+                };
+                let frame = &frames[frame];
+                self.draw_moving_sprite_frame_in_level(
+                    frame.x as usize,
+                    frame.y as usize,
+                    K_TILE_SIZE * 2,
+                    K_TILE_SIZE,
+                    dst_x - K_TILE_SIZE as i32,
+                    dst_y,
+                );
+            }
+            // The object hasn't started moving yet
+            _ => self.draw_fixed_tile_in_level(tile.tile, dst_x, dst_y),
+        }
+    }
+
+    fn draw_murphy(&mut self, states: &GameStates) {
+        let location = states.g_murphy_location;
+        if location >= K_LEVEL_SIZE
+            || states.g_current_level_state[location].tile != LevelTileType::Murphy as u8
+        {
+            return;
+        }
+
+        let state = states.g_current_level_state[location].state;
+        if state == 0 {
+            if let Some((animation_index, frame)) = states.murphy_idle_frame() {
+                let frame = &K_MURPHY_ANIMATION_FRAME_COORDINATES[animation_index][frame];
+                self.draw_moving_sprite_frame_in_level(
+                    frame.x as usize,
+                    frame.y as usize,
+                    K_TILE_SIZE,
+                    K_TILE_SIZE,
+                    states.g_murphy_position_x as i32,
+                    states.g_murphy_position_y as i32,
+                );
+            }
+            return;
+        }
+
+        let animation = &states.g_current_murphy_animation;
+        let frames = K_MURPHY_ANIMATION_FRAME_COORDINATES[animation.animation_index as usize];
+        let frame = &frames[(animation.current_frame as usize).min(frames.len() - 1)];
+        let width = animation.width as usize * 8;
+        let height = animation.height as usize;
+
+        let action = state & 0xFC;
+        if action == K_MURPHY_STATE_PASSING_PORT {
+            self.draw_moving_sprite_frame_in_level(
+                frame.x as usize,
+                frame.y as usize,
+                width,
+                height,
+                states.g_murphy_position_x as i32,
+                states.g_murphy_position_y as i32,
+            );
+            return;
+        }
+
+        if action == K_MURPHY_STATE_SNAPPING_BASE
+            || action == K_MURPHY_STATE_SNAPPING_INFOTRON
+            || action == K_MURPHY_STATE_SNAPPING_RED_DISK
+        {
+            self.draw_fixed_tile(LevelTileType::Murphy as u8, location);
+        }
+
+        // The offset comes from the original game, where the level was drawn in a bitmap of 122 bytes
+        // per line, each byte being 8 pixels wide
+        let offset = animation.animation_coordinates_offset as i32;
+        let offset_y = (offset + 61).div_euclid(122);
+        let offset_x = (offset - offset_y * 122) * 8;
+
+        let anchor = states.g_murphy_animation_anchor_location;
+        let dst_x = ((anchor % K_LEVEL_WIDTH) * K_TILE_SIZE) as i32 + offset_x;
+        let dst_y = ((anchor / K_LEVEL_WIDTH) * K_TILE_SIZE) as i32 + offset_y;
+
+        self.draw_moving_sprite_frame_in_level(
+            frame.x as usize,
+            frame.y as usize,
+            width,
+            height,
+            dst_x,
+            dst_y,
+        );
+    }
+
     pub fn draw_moving_frame(&mut self, src_x: usize, src_y: usize, dest_position: usize) {
         if self.fast_mode == FastModeType::Ultra {
             return;
@@ -1204,8 +1544,8 @@ impl Graphics<'_> {
         // - di: coordinates on the screen
         // - si: coordinates on the MOVING.DAT bitmap to draw from?
 
-        let dest_x = (dest_position % K_LEVEL_WIDTH) * K_TILE_SIZE;
-        let dest_y = (dest_position / K_LEVEL_WIDTH) * K_TILE_SIZE;
+        let dest_x = ((dest_position % K_LEVEL_WIDTH) * K_TILE_SIZE) as i32;
+        let dest_y = ((dest_position / K_LEVEL_WIDTH) * K_TILE_SIZE) as i32;
 
         self.draw_moving_sprite_frame_in_level(
             src_x,
@@ -1270,7 +1610,7 @@ const K_PALETTE_DATA_SIZE: usize = 64;
 
 pub const K_MOVING_BITMAP_WIDTH: usize = 320;
 const K_MOVING_BITMAP_HEIGHT: usize = 462;
-pub const K_FIXED_BITMAP_WIDTH: usize = 460;
+pub const K_FIXED_BITMAP_WIDTH: usize = 640;
 const K_FIXED_BITMAP_HEIGHT: usize = 16;
 const K_PANEL_BITMAP_WIDTH: usize = 320;
 pub const K_PANEL_BITMAP_HEIGHT: usize = 24;
@@ -1289,6 +1629,12 @@ pub const K_LEVEL_BITMAP_WIDTH: usize =
 pub const K_LEVEL_BITMAP_HEIGHT: usize =
     K_TILE_SIZE * (K_LEVEL_HEIGHT - 2) + K_LEVEL_EDGE_SIZE + K_LEVEL_EDGE_SIZE;
 
+// Area of the terminal tile in FIXED.DAT that scrolls
+const K_TERMINAL_SCREEN_X: usize = 2;
+const K_TERMINAL_SCREEN_Y: usize = 2;
+const K_TERMINAL_SCREEN_WIDTH: usize = 9;
+const K_TERMINAL_SCREEN_HEIGHT: usize = 8;
+
 pub const G_BLACK_PALETTE: ColorPalette = [Color {
     r: 0,
     g: 0,
@@ -274,7 +274,7 @@ impl Keys {
     }
 }
 
-#[derive(Default, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Default, Clone, Copy, PartialEq, PartialOrd, Debug)]
 pub enum UserInput {
     #[default]
     None = 0,
@@ -289,30 +289,27 @@ pub enum UserInput {
     SpaceOnly = 9,
 }
 
+impl From<u8> for UserInput {
+    /// Values out of the range of user inputs are converted to UserInput::None
+    fn from(value: u8) -> Self {
+        match value {
+            1 => UserInput::Up,
+            2 => UserInput::Left,
+            3 => UserInput::Down,
+            4 => UserInput::Right,
+            5 => UserInput::SpaceUp,
+            6 => UserInput::SpaceLeft,
+            7 => UserInput::SpaceDown,
+            8 => UserInput::SpaceRight,
+            9 => UserInput::SpaceOnly,
+            _ => UserInput::None,
+        }
+    }
+}
+
 impl AddAssign<UserInput> for UserInput {
     fn add_assign(&mut self, other: Self) {
-        let value = *self as u8 + other as u8;
-        if value == 0 {
-            *self = UserInput::None;
-        } else if value == 1 {
-            *self = UserInput::Up;
-        } else if value == 2 {
-            *self = UserInput::Left;
-        } else if value == 3 {
-            *self = UserInput::Down;
-        } else if value == 4 {
-            *self = UserInput::Right;
-        } else if value == 5 {
-            *self = UserInput::SpaceUp;
-        } else if value == 6 {
-            *self = UserInput::SpaceLeft;
-        } else if value == 7 {
-            *self = UserInput::SpaceDown;
-        } else if value == 8 {
-            *self = UserInput::SpaceRight;
-        } else if value == 0 {
-            *self = UserInput::SpaceOnly;
-        }
+        *self = UserInput::from(*self as u8 + other as u8);
     }
 }
 
@@ -15,72 +15,170 @@
 * along with this program. If not, see <http://www.gnu.org/licenses/>.
 */
 
-use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom},
-    path::Path,
-};
+use std::fs;
+use std::path::Path;
 
 use crate::game::globals::*;
+use crate::game::level_codec;
+use crate::game::level_text;
+use crate::game::mpx::{self, MpxLevel};
 
 pub struct LevelManager {
-    pub g_level_list_data: Box<[Box<Level>; K_NUMBER_OF_LEVEL_WITH_PADDING]>,
+    pub g_level_list_data: Vec<Level>, // The levels, followed by K_LEVEL_LIST_PADDING list entries
     pub g_levels_dat_filename: String,
+    pub g_levels_dat_extra_data: Vec<u8>, // Bytes found after the last level, kept to write them back
+    pub g_number_of_levels: usize,        // Number of levels of the level set file
+    pub g_level_list_demos: Vec<Vec<u8>>, // Demos recorded on each level, only found in MPX files
 }
 
 impl LevelManager {
     pub fn new() -> LevelManager {
-        LevelManager {
-            g_level_list_data: Box::new(
-                [(); K_NUMBER_OF_LEVEL_WITH_PADDING].map(|_| Box::new(Level::new())),
-            ),
+        let mut level_manager = LevelManager {
+            g_level_list_data: Vec::new(),
             g_levels_dat_filename: String::from("LEVELS.DAT"),
-        }
+            g_levels_dat_extra_data: Vec::new(),
+            g_number_of_levels: K_NUMBER_OF_LEVELS,
+            g_level_list_demos: Vec::new(),
+        };
+        level_manager.reset_level_list(K_NUMBER_OF_LEVELS);
+        level_manager
+    }
+
+    /// Index in the list of levels of "- REPLAY SKIPPED LEVELS!! -", which comes after the levels
+    pub fn last_level_index(&self) -> usize {
+        K_FIRST_LEVEL_INDEX + self.g_number_of_levels
     }
 
     pub fn read_levels_lst(&mut self) {
-        // Re-init g_level_list_data
-        self.g_level_list_data =
-            Box::new([(); K_NUMBER_OF_LEVEL_WITH_PADDING].map(|_| Box::new(Level::new())));
-        self.g_level_list_data[K_LAST_LEVEL_INDEX].name =
-            String::from("- REPLAY SKIPPED LEVELS!! -");
-        self.g_level_list_data[K_LAST_LEVEL_INDEX + 1].name =
+        let path = format!("{}/{}", RESSOURCES_PATH, self.g_levels_dat_filename);
+        self.read_level_set(&path)
+            .unwrap_or_else(|error| panic!("{}", error));
+    }
+
+    /// Reads the level list from the file at the given path, either a LEVELS.DAT, a MPX or a text
+    /// file. MPX files are recognized by their content, so they can be named like LEVELS.DAT files
+    /// to be played. Text files are recognized by their .TXT extension.
+    pub fn read_level_set(&mut self, path: &str) -> Result<(), String> {
+        let file_data =
+            fs::read(path).map_err(|error| format!("Can't read {}: {}", path, error))?;
+        if mpx::is_mpx(&file_data) {
+            mpx::read_mpx(&file_data).and_then(|levels| self.load_mpx_levels(levels))
+        } else if has_extension(path, "txt") {
+            String::from_utf8(file_data)
+                .map_err(|_| String::from("The file is not a text file"))
+                .and_then(|text| level_text::text_to_levels(&text))
+                .and_then(|levels| self.load_levels(levels))
+        } else {
+            self.load_levels_dat(&file_data)
+        }
+        .map_err(|error| format!("{}: {}", path, error))
+    }
+
+    fn reset_level_list(&mut self, number_of_levels: usize) {
+        self.g_number_of_levels = number_of_levels;
+        self.g_level_list_data = vec![Level::new(); number_of_levels + K_LEVEL_LIST_PADDING];
+        let last_level_index = self.last_level_index();
+        self.g_level_list_data[last_level_index].name = String::from("- REPLAY SKIPPED LEVELS!! -");
+        self.g_level_list_data[last_level_index + 1].name =
             String::from("---- UNBELIEVEABLE!!!! ----");
+        self.g_levels_dat_extra_data.clear();
+        self.g_level_list_demos.clear();
+    }
 
-        let path = format!("{}/{}", RESSOURCES_PATH, self.g_levels_dat_filename);
-        let level_lst_file_path = Path::new(&path);
-        match level_lst_file_path.try_exists().expect(
-            format!(
-                "Can't check existence of file {}",
-                self.g_levels_dat_filename
-            )
-            .as_str(),
-        ) {
-            true => (),
-            false => panic!("{:?} doesn't exists", level_lst_file_path.canonicalize()),
+    /// Reads the levels of a LEVELS.DAT file, as many as the file holds. Bytes after the last level,
+    /// less than a level, are kept aside.
+    fn load_levels_dat(&mut self, file_data: &[u8]) -> Result<(), String> {
+        let number_of_levels = file_data.len() / K_LEVEL_DATA_LENGTH;
+        if number_of_levels == 0 {
+            return Err(format!(
+                "The file has {} bytes, less than a level",
+                file_data.len()
+            ));
+        }
+
+        self.reset_level_list(number_of_levels);
+        let levels_length = number_of_levels * K_LEVEL_DATA_LENGTH;
+        for (i, level_data) in file_data[..levels_length]
+            .chunks_exact(K_LEVEL_DATA_LENGTH)
+            .enumerate()
+        {
+            self.g_level_list_data[i] = Level::from_raw(i, level_data)
+                .map_err(|error| format!("Level {}: {}", i + 1, error))?;
+        }
+        self.g_levels_dat_extra_data = file_data[levels_length..].to_vec();
+        Ok(())
+    }
+
+    fn load_mpx_levels(&mut self, levels: Vec<MpxLevel>) -> Result<(), String> {
+        let (levels, demos) = levels
+            .into_iter()
+            .map(|mpx_level| (mpx_level.level, mpx_level.demo_data))
+            .unzip();
+        self.load_levels(levels)?;
+        self.g_level_list_demos = demos;
+        Ok(())
+    }
+
+    fn load_levels(&mut self, levels: Vec<Level>) -> Result<(), String> {
+        if levels.is_empty() {
+            return Err(String::from("The level set has no level"));
         }
-        let mut file = File::open(level_lst_file_path)
-            .expect(format!("Error while opening {}", self.g_levels_dat_filename).as_str());
-
-        let mut file_data = [0_u8; K_LEVEL_DATA_LENGTH];
-
-        for i in 0..K_NUMBER_OF_LEVELS {
-            let seek_offset = i * K_LEVEL_DATA_LENGTH;
-            file.seek(SeekFrom::Start(seek_offset as u64)).expect(
-                format!(
-                    "Error while seeking offset {} in {}",
-                    seek_offset, G_LEVELS_LST_FILENAME
-                )
-                .as_str(),
-            );
-            file.read(&mut file_data)
-                .expect(format!("Error while reading {}", G_LEVELS_LST_FILENAME).as_str());
-
-            let level = Level::from_raw(i, file_data);
-
-            self.g_level_list_data[i] = Box::new(level);
+
+        self.reset_level_list(levels.len());
+        for (i, level) in levels.into_iter().enumerate() {
+            self.g_level_list_data[i] = level;
+        }
+        Ok(())
+    }
+
+    /// Returns the content of the LEVELS.DAT file of the level list
+    pub fn levels_dat_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(
+            self.g_number_of_levels * K_LEVEL_DATA_LENGTH + self.g_levels_dat_extra_data.len(),
+        );
+        for level in &self.g_level_list_data[..self.g_number_of_levels] {
+            data.extend_from_slice(&level.to_raw());
         }
+        data.extend_from_slice(&self.g_levels_dat_extra_data);
+        data
     }
+
+    /// Returns the content of the MPX file of the level list, with the demos of the levels
+    pub fn mpx_data(&self) -> Result<Vec<u8>, String> {
+        let levels: Vec<MpxLevel> = self.g_level_list_data[..self.g_number_of_levels]
+            .iter()
+            .enumerate()
+            .map(|(i, level)| MpxLevel {
+                level: level.clone(),
+                demo_data: self.g_level_list_demos.get(i).cloned().unwrap_or_default(),
+            })
+            .collect();
+        mpx::write_mpx(&levels)
+    }
+
+    /// Returns the levels of the level list written as text
+    pub fn text_data(&self) -> String {
+        level_text::levels_to_text(&self.g_level_list_data[..self.g_number_of_levels])
+    }
+
+    /// Writes the level list into the file at the given path, a MPX file when its extension is
+    /// .MPX, a text file when it is .TXT, and a LEVELS.DAT file otherwise
+    pub fn write_level_set(&self, path: &str) -> Result<(), String> {
+        let file_data = if has_extension(path, "mpx") {
+            self.mpx_data()?
+        } else if has_extension(path, "txt") {
+            self.text_data().into_bytes()
+        } else {
+            self.levels_dat_data()
+        };
+        fs::write(path, file_data).map_err(|error| format!("Can't write {}: {}", path, error))
+    }
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|path_extension| path_extension.eq_ignore_ascii_case(extension))
 }
 
 #[derive(Clone)]
@@ -126,6 +224,9 @@ pub struct Level {
     // seed for the next calculation: new_random_number_seed = ((old_random_number_seed * 1509) + 49) modulo 65536
     // This "modulo 65536" just signifies keeping only the lower 16 bits and reject all higher bits.
     pub random_seed: u16,
+
+    // Position of the level in its level set, starting at 1. It's not part of the level data.
+    pub number: usize,
 } // size 1536 = 0x600
 
 impl Level {
@@ -140,93 +241,29 @@ impl Level {
             speed_fix_magic_number: 0,
             name: String::new(),
             random_seed: 0,
+            number: 0,
             special_ports_info: [SpecialPortInfo::default(); K_LEVEL_MAX_NUMBER_OF_SPECIAL_PORTS],
             tiles: [(); K_LEVEL_SIZE].map(|_| 0),
             unused: [0; 4],
         }
     }
 
-    pub fn from_raw(level_index: usize, raw_data: [u8; K_LEVEL_DATA_LENGTH]) -> Level {
-        // TODO : fix type and data size issue
-        let mut level = Level::new();
-
-        const OFFSET_TILES: usize = 0;
-        const OFFSET_UNUSED: usize = OFFSET_TILES + K_LEVEL_SIZE;
-        const OFFSET_INITIAL_GRAVITATION: usize = OFFSET_UNUSED + 4;
-        const OFFSET_SPEED_FIX_MAGIC_NUMBER: usize = OFFSET_INITIAL_GRAVITATION + 1;
-        const OFFSET_LEVEL_NAME: usize = OFFSET_SPEED_FIX_MAGIC_NUMBER + 1;
-        const OFFSET_FREEZE_ZONKS: usize = OFFSET_LEVEL_NAME + K_LEVEL_NAME_LENGTH - 1;
-        const OFFSET_NUMBER_OF_INFOTRONS: usize = OFFSET_FREEZE_ZONKS + 1;
-        const OFFSET_NUMBER_OF_SP: usize = OFFSET_NUMBER_OF_INFOTRONS + 1;
-        const OFFSET_SP_0: usize = OFFSET_NUMBER_OF_SP + 1;
-        const OFFSET_SP_1: usize = OFFSET_SP_0 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SP_2: usize = OFFSET_SP_1 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SP_3: usize = OFFSET_SP_2 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SP_4: usize = OFFSET_SP_3 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SP_5: usize = OFFSET_SP_4 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SP_6: usize = OFFSET_SP_5 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SP_7: usize = OFFSET_SP_6 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SP_8: usize = OFFSET_SP_7 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SP_9: usize = OFFSET_SP_8 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SCRAMBLED_CHECKSUM: usize = OFFSET_SP_9 + K_SPECIAL_PORT_STRUCT_SIZE;
-        const OFFSET_SCRAMBLED_SPEED: usize = OFFSET_SCRAMBLED_CHECKSUM + 1;
-        const OFFSET_RANDOM_SEED: usize = OFFSET_SCRAMBLED_SPEED;
-
-        level.freeze_zonks = raw_data[OFFSET_FREEZE_ZONKS];
-        level.initial_gravitation = raw_data[OFFSET_INITIAL_GRAVITATION];
-        level.number_of_infotrons = raw_data[OFFSET_NUMBER_OF_INFOTRONS];
-        level.number_of_special_ports = raw_data[OFFSET_NUMBER_OF_SP];
-        level.scrambled_checksum = raw_data[OFFSET_SCRAMBLED_CHECKSUM];
-        level.scrambled_speed = raw_data[OFFSET_SCRAMBLED_SPEED];
-        level.speed_fix_magic_number = raw_data[OFFSET_SPEED_FIX_MAGIC_NUMBER];
-        level.name = format!(
-            "{:03} {}",
-            level_index + 1,
-            String::from_utf8_lossy(
-                &raw_data[OFFSET_LEVEL_NAME..(OFFSET_LEVEL_NAME + K_LEVEL_NAME_LENGTH - 1)]
-            )
-        );
-        level.random_seed =
-            (raw_data[OFFSET_RANDOM_SEED] as u16) << 8 | raw_data[OFFSET_RANDOM_SEED + 1] as u16; // LE or BE ?
-        level.special_ports_info[0] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_0..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_0)],
-        );
-        level.special_ports_info[1] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_1..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_1)],
-        );
-        level.special_ports_info[2] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_2..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_2)],
-        );
-        level.special_ports_info[3] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_3..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_3)],
-        );
-        level.special_ports_info[4] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_4..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_4)],
-        );
-        level.special_ports_info[5] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_5..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_5)],
-        );
-        level.special_ports_info[6] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_6..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_6)],
-        );
-        level.special_ports_info[7] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_7..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_7)],
-        );
-        level.special_ports_info[8] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_8..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_8)],
-        );
-        level.special_ports_info[9] = SpecialPortInfo::from_raw(
-            &raw_data[OFFSET_SP_9..(K_SPECIAL_PORT_STRUCT_SIZE + OFFSET_SP_9)],
-        );
+    /// Reads the level at the given index of its level set from its 1536 bytes
+    pub fn from_raw(level_index: usize, raw_data: &[u8]) -> Result<Level, String> {
+        level_codec::decode_level(level_index + 1, raw_data)
+    }
 
-        for i in 0..K_LEVEL_SIZE {
-            level.tiles[i] = raw_data[i];
-        }
+    /// Converts the level back into the 1536 bytes it is stored as in LEVELS.DAT and .SP files
+    pub fn to_raw(&self) -> [u8; K_LEVEL_DATA_LENGTH] {
+        level_codec::encode_level(self)
+    }
 
-        for i in 0..4 {
-            level.unused[i] = raw_data[OFFSET_UNUSED + i];
+    /// Returns the name of the level as shown in the list of levels, like "001 ------- WARM UP -------"
+    pub fn list_name(&self) -> String {
+        if self.number == 0 {
+            // Entries of the list that are not levels, like "- REPLAY SKIPPED LEVELS!! -"
+            return self.name.clone();
         }
-
-        level
+        format!("{:03} {}", self.number, self.name)
     }
 }
@@ -0,0 +1,297 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Binary codec of the 0x600 bytes of a level, as stored in LEVELS.DAT and .SP files. The layout is
+// declared once in level_data_layout, which is walked by the decoder to fill a level and by the
+// encoder to write it back.
+
+use crate::game::globals::*;
+use crate::game::level::Level;
+
+/// Reads or writes each field of the level data, in the order of the layout
+trait LevelDataCodec {
+    fn bytes(&mut self, field: &mut [u8]);
+    fn u8(&mut self, field: &mut u8);
+    fn u16_le(&mut self, field: &mut u16);
+    fn u16_be(&mut self, field: &mut u16);
+    fn text(&mut self, field: &mut String, length: usize);
+}
+
+/// Layout of the level data (0x600 bytes)
+fn level_data_layout(codec: &mut impl LevelDataCodec, level: &mut Level) {
+    codec.bytes(&mut level.tiles); // 0x000
+    codec.bytes(&mut level.unused); // 0x5A0
+    codec.u8(&mut level.initial_gravitation); // 0x5A4
+    codec.u8(&mut level.speed_fix_magic_number); // 0x5A5
+    codec.text(&mut level.name, K_LEVEL_NAME_LENGTH - 1); // 0x5A6
+    codec.u8(&mut level.freeze_zonks); // 0x5BD
+    codec.u8(&mut level.number_of_infotrons); // 0x5BE
+    codec.u8(&mut level.number_of_special_ports); // 0x5BF
+    for special_port_info in level.special_ports_info.iter_mut() {
+        // 0x5C0, 6 bytes per special port
+        codec.u16_be(&mut special_port_info.position);
+        codec.u8(&mut special_port_info.gravity);
+        codec.u8(&mut special_port_info.freeze_zonks);
+        codec.u8(&mut special_port_info.freeze_enemies);
+        codec.u8(&mut special_port_info.unused);
+    }
+    codec.u8(&mut level.scrambled_speed); // 0x5FC
+    codec.u8(&mut level.scrambled_checksum); // 0x5FD
+    codec.u16_le(&mut level.random_seed); // 0x5FE
+}
+
+struct LevelDataDecoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl LevelDataDecoder<'_> {
+    fn next_bytes(&mut self, length: usize) -> &[u8] {
+        let bytes = &self.data[self.offset..(self.offset + length)];
+        self.offset += length;
+        bytes
+    }
+}
+
+impl LevelDataCodec for LevelDataDecoder<'_> {
+    fn bytes(&mut self, field: &mut [u8]) {
+        field.copy_from_slice(self.next_bytes(field.len()));
+    }
+
+    fn u8(&mut self, field: &mut u8) {
+        *field = self.next_bytes(1)[0];
+    }
+
+    fn u16_le(&mut self, field: &mut u16) {
+        let bytes = self.next_bytes(2);
+        *field = u16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+
+    fn u16_be(&mut self, field: &mut u16) {
+        let bytes = self.next_bytes(2);
+        *field = u16::from_be_bytes([bytes[0], bytes[1]]);
+    }
+
+    fn text(&mut self, field: &mut String, length: usize) {
+        // Each byte is read as a char, so that the text is written back as it was
+        *field = self
+            .next_bytes(length)
+            .iter()
+            .map(|&character| character as char)
+            .collect();
+    }
+}
+
+struct LevelDataEncoder {
+    data: Vec<u8>,
+}
+
+impl LevelDataCodec for LevelDataEncoder {
+    fn bytes(&mut self, field: &mut [u8]) {
+        self.data.extend_from_slice(field);
+    }
+
+    fn u8(&mut self, field: &mut u8) {
+        self.data.push(*field);
+    }
+
+    fn u16_le(&mut self, field: &mut u16) {
+        self.data.extend_from_slice(&field.to_le_bytes());
+    }
+
+    fn u16_be(&mut self, field: &mut u16) {
+        self.data.extend_from_slice(&field.to_be_bytes());
+    }
+
+    fn text(&mut self, field: &mut String, length: usize) {
+        // Texts are padded with spaces, and chars that don't fit in a byte are replaced by spaces
+        let mut text: Vec<u8> = field
+            .chars()
+            .take(length)
+            .map(|character| u8::try_from(character).unwrap_or(b' '))
+            .collect();
+        text.resize(length, b' ');
+        self.data.extend_from_slice(&text);
+    }
+}
+
+/// Reads the level with the given number from its level data, and checks it can be played
+pub fn decode_level(level_number: usize, level_data: &[u8]) -> Result<Level, String> {
+    if level_data.len() != K_LEVEL_DATA_LENGTH {
+        return Err(format!(
+            "The level data has {} bytes instead of {}",
+            level_data.len(),
+            K_LEVEL_DATA_LENGTH
+        ));
+    }
+
+    let mut level = Level::new();
+    let mut decoder = LevelDataDecoder {
+        data: level_data,
+        offset: 0,
+    };
+    level_data_layout(&mut decoder, &mut level);
+    level.number = level_number;
+
+    validate_level(&level)?;
+    Ok(level)
+}
+
+/// Writes the level data of the given level
+pub fn encode_level(level: &Level) -> [u8; K_LEVEL_DATA_LENGTH] {
+    let mut encoder = LevelDataEncoder {
+        data: Vec::with_capacity(K_LEVEL_DATA_LENGTH),
+    };
+    // The layout needs mutable fields to be shared with the decoder, the level is left unchanged
+    level_data_layout(&mut encoder, &mut level.clone());
+    encoder.data.try_into().unwrap()
+}
+
+/// Checks the special ports of the level: there are at most 10 of them, all inside the level
+pub fn validate_level(level: &Level) -> Result<(), String> {
+    if level.number_of_special_ports as usize > K_LEVEL_MAX_NUMBER_OF_SPECIAL_PORTS {
+        return Err(format!(
+            "The level has {} special ports, the maximum is {}",
+            level.number_of_special_ports, K_LEVEL_MAX_NUMBER_OF_SPECIAL_PORTS
+        ));
+    }
+
+    let special_ports_info = &level.special_ports_info[..level.number_of_special_ports as usize];
+    for (i, special_port_info) in special_ports_info.iter().enumerate() {
+        // Positions count 2 bytes per tile
+        let position = special_port_info.position as usize;
+        if position & 1 != 0 || position / 2 >= K_LEVEL_SIZE {
+            return Err(format!(
+                "The special port {} is at position 0x{:04X}, outside of the {}x{} level",
+                i + 1,
+                position,
+                K_LEVEL_WIDTH,
+                K_LEVEL_HEIGHT
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const K_OFFSET_NUMBER_OF_SPECIAL_PORTS: usize = 0x5BF;
+    const K_OFFSET_FIRST_SPECIAL_PORT: usize = 0x5C0;
+    const K_OFFSET_RANDOM_SEED: usize = 0x5FE;
+
+    fn read_level_sets() -> Vec<(String, Vec<u8>)> {
+        let resources_path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), RESSOURCES_PATH);
+        let mut level_sets: Vec<(String, Vec<u8>)> = fs::read_dir(resources_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("LEVELS.D")
+            })
+            .map(|path| (path.to_string_lossy().to_string(), fs::read(&path).unwrap()))
+            .collect();
+        level_sets.sort();
+        level_sets
+    }
+
+    fn first_level_data() -> Vec<u8> {
+        let levels_dat_path = format!(
+            "{}/{}/LEVELS.DAT",
+            env!("CARGO_MANIFEST_DIR"),
+            RESSOURCES_PATH
+        );
+        fs::read(levels_dat_path).unwrap()[..K_LEVEL_DATA_LENGTH].to_vec()
+    }
+
+    #[test]
+    fn shipped_level_sets_are_decoded_and_encoded_back_identically() {
+        let level_sets = read_level_sets();
+        assert!(level_sets.len() > 1);
+
+        for (filename, level_set_data) in level_sets {
+            for (i, level_data) in level_set_data.chunks_exact(K_LEVEL_DATA_LENGTH).enumerate() {
+                let level = decode_level(i + 1, level_data)
+                    .unwrap_or_else(|error| panic!("Level {} of {}: {}", i + 1, filename, error));
+                assert_eq!(
+                    encode_level(&level).as_slice(),
+                    level_data,
+                    "Level {} of {}",
+                    i + 1,
+                    filename
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn first_shipped_level_is_decoded() {
+        let level = decode_level(1, &first_level_data()).unwrap();
+        assert_eq!(level.name, "------- WARM UP -------");
+        assert_eq!(level.number, 1);
+        assert_eq!(level.list_name(), "001 ------- WARM UP -------");
+    }
+
+    #[test]
+    fn fields_are_decoded_with_their_endianness() {
+        let mut level_data = first_level_data();
+        level_data[K_OFFSET_NUMBER_OF_SPECIAL_PORTS] = 1;
+        level_data[K_OFFSET_FIRST_SPECIAL_PORT] = 0x05;
+        level_data[K_OFFSET_FIRST_SPECIAL_PORT + 1] = 0x0A;
+        level_data[K_OFFSET_RANDOM_SEED] = 0x34;
+        level_data[K_OFFSET_RANDOM_SEED + 1] = 0x12;
+
+        let level = decode_level(1, &level_data).unwrap();
+        assert_eq!(level.special_ports_info[0].position, 0x050A);
+        assert_eq!(level.random_seed, 0x1234);
+        assert_eq!(encode_level(&level).as_slice(), level_data.as_slice());
+    }
+
+    #[test]
+    fn level_data_with_a_wrong_size_is_rejected() {
+        let level_data = first_level_data();
+        assert!(decode_level(1, &level_data[..K_LEVEL_DATA_LENGTH - 1]).is_err());
+        assert!(decode_level(1, &[level_data.as_slice(), &[0]].concat()).is_err());
+    }
+
+    #[test]
+    fn levels_with_too_many_special_ports_are_rejected() {
+        let mut level_data = first_level_data();
+        level_data[K_OFFSET_NUMBER_OF_SPECIAL_PORTS] = 11;
+        assert!(decode_level(1, &level_data).is_err());
+    }
+
+    #[test]
+    fn special_ports_outside_of_the_level_are_rejected() {
+        let mut level_data = first_level_data();
+        level_data[K_OFFSET_NUMBER_OF_SPECIAL_PORTS] = 1;
+        // Position of the tile right after the last one of the level
+        let position = (K_LEVEL_SIZE * 2) as u16;
+        level_data[K_OFFSET_FIRST_SPECIAL_PORT..(K_OFFSET_FIRST_SPECIAL_PORT + 2)]
+            .copy_from_slice(&position.to_be_bytes());
+        assert!(decode_level(1, &level_data).is_err());
+
+        // Positions count 2 bytes per tile, so they can't be odd
+        level_data[K_OFFSET_FIRST_SPECIAL_PORT..(K_OFFSET_FIRST_SPECIAL_PORT + 2)]
+            .copy_from_slice(&1_u16.to_be_bytes());
+        assert!(decode_level(1, &level_data).is_err());
+    }
+}
@@ -0,0 +1,601 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Text format of level sets, to keep levels in version control and review their changes.
+// Converting a level set to text and back gives the same levels, byte for byte.
+//
+// # Comments start with a hash, outside of the tiles
+// level: 1
+// name: "------- WARM UP -------"
+// gravity: off
+// freeze-zonks: off
+// infotrons-needed: 0
+// special-port: 12,5 gravity=on freeze-zonks=off freeze-enemies=off
+// tiles:
+// ############################################################
+// #OO..I+++++++...
+//   <24 lines of 60 tiles, one character per tile>
+//
+// Flags are on, off, or the value stored in the level when it's neither. 0 infotrons needed means
+// all the infotrons of the level. Positions are x,y from the top-left corner of the level.
+// Data that the game ignores or computes is only written when it's not 0, to keep it as it was:
+// random-seed, speed-fix-version, scrambled-speed, scrambled-checksum, unused (4 bytes in
+// hexadecimal), and unused-special-port for the data after the last special port. Tiles without
+// character are written as ? in the grid, with their value given by an unknown-tile line like
+// "unknown-tile: 12,5 0x4F".
+
+use std::fmt::Write;
+
+use crate::game::globals::*;
+use crate::game::level::Level;
+use crate::game::level_codec;
+use crate::game::utils::{escape_text, unescape_text};
+
+// Character and name of each tile, indexed by their value. 40 is not a tile of the game, but many
+// levels have it.
+const K_LEVEL_TILE_CHARACTERS: [(char, &str); 41] = [
+    ('.', "space"),
+    ('O', "zonk"),
+    ('+', "base"),
+    ('M', "murphy"),
+    ('I', "infotron"),
+    ('C', "chip"),
+    ('#', "hardware"),
+    ('E', "exit"),
+    ('o', "orange disk"),
+    ('>', "port right"),
+    ('v', "port down"),
+    ('<', "port left"),
+    ('^', "port up"),
+    ('R', "special port right"),
+    ('D', "special port down"),
+    ('L', "special port left"),
+    ('U', "special port up"),
+    ('S', "snik snak"),
+    ('Y', "yellow disk"),
+    ('T', "terminal"),
+    ('r', "red disk"),
+    ('|', "port vertical"),
+    ('-', "port horizontal"),
+    ('*', "port 4 way"),
+    ('e', "electron"),
+    ('B', "bug"),
+    ('[', "chip left"),
+    (']', "chip right"),
+    ('2', "hardware 2"),
+    ('3', "hardware 3"),
+    ('4', "hardware 4"),
+    ('X', "explosion"),
+    ('6', "hardware 6"),
+    ('7', "hardware 7"),
+    ('8', "hardware 8"),
+    ('9', "hardware 9"),
+    ('0', "hardware 10"),
+    ('1', "hardware 11"),
+    ('n', "chip top"),
+    ('u', "chip bottom"),
+    ('~', "tile 40"),
+];
+const K_UNKNOWN_TILE_CHARACTER: char = '?';
+const K_LEVEL_TILES_PER_LEGEND_LINE: usize = 4;
+
+/// Writes the levels of a level set as text
+pub fn levels_to_text(levels: &[Level]) -> String {
+    let mut text = String::from("# Rustaplex level set\n");
+    writeln!(text, "# {} levels, tiles are written as:", levels.len()).unwrap();
+    for legend_line in K_LEVEL_TILE_CHARACTERS.chunks(K_LEVEL_TILES_PER_LEGEND_LINE) {
+        text.push('#');
+        for (character, name) in legend_line {
+            write!(text, "  {} {:<18}", character, name).unwrap();
+        }
+        text.truncate(text.trim_end().len());
+        text.push('\n');
+    }
+
+    for (i, level) in levels.iter().enumerate() {
+        text.push('\n');
+        writeln!(text, "level: {}", i + 1).unwrap();
+        write_level(&mut text, level);
+    }
+    text
+}
+
+fn write_level(text: &mut String, level: &Level) {
+    let name: Vec<u8> = level
+        .name
+        .chars()
+        .map(|character| character as u8)
+        .collect();
+    writeln!(text, "name: {}", escape_text(&name)).unwrap();
+    writeln!(
+        text,
+        "gravity: {}",
+        flag_to_text(level.initial_gravitation, 1)
+    )
+    .unwrap();
+    writeln!(
+        text,
+        "freeze-zonks: {}",
+        flag_to_text(level.freeze_zonks, 2)
+    )
+    .unwrap();
+    writeln!(text, "infotrons-needed: {}", level.number_of_infotrons).unwrap();
+
+    let number_of_special_ports = level.number_of_special_ports as usize;
+    for (i, special_port_info) in level.special_ports_info.iter().enumerate() {
+        let key = if i < number_of_special_ports {
+            "special-port"
+        } else if level.special_ports_info[i..]
+            .iter()
+            .any(|special_port_info| !is_empty_special_port(special_port_info))
+        {
+            "unused-special-port"
+        } else {
+            break;
+        };
+        write!(
+            text,
+            "{}: {} gravity={} freeze-zonks={} freeze-enemies={}",
+            key,
+            special_port_position_to_text(special_port_info.position),
+            flag_to_text(special_port_info.gravity, 1),
+            flag_to_text(special_port_info.freeze_zonks, 2),
+            flag_to_text(special_port_info.freeze_enemies, 1)
+        )
+        .unwrap();
+        if special_port_info.unused != 0 {
+            write!(text, " unused=0x{:02X}", special_port_info.unused).unwrap();
+        }
+        text.push('\n');
+    }
+
+    if level.random_seed != 0 {
+        writeln!(text, "random-seed: 0x{:04X}", level.random_seed).unwrap();
+    }
+    for (key, value) in [
+        ("speed-fix-version", level.speed_fix_magic_number),
+        ("scrambled-speed", level.scrambled_speed),
+        ("scrambled-checksum", level.scrambled_checksum),
+    ] {
+        if value != 0 {
+            writeln!(text, "{}: 0x{:02X}", key, value).unwrap();
+        }
+    }
+    if level.unused != [0; 4] {
+        let unused: String = level
+            .unused
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        writeln!(text, "unused: {}", unused).unwrap();
+    }
+
+    for (tile_index, &tile) in level.tiles.iter().enumerate() {
+        if K_LEVEL_TILE_CHARACTERS.get(tile as usize).is_none() {
+            writeln!(
+                text,
+                "unknown-tile: {} 0x{:02X}",
+                tile_index_to_text(tile_index),
+                tile
+            )
+            .unwrap();
+        }
+    }
+
+    text.push_str("tiles:\n");
+    for row in level.tiles.chunks(K_LEVEL_WIDTH) {
+        for &tile in row {
+            let character = match K_LEVEL_TILE_CHARACTERS.get(tile as usize) {
+                Some((character, _)) => *character,
+                None => K_UNKNOWN_TILE_CHARACTER,
+            };
+            text.push(character);
+        }
+        text.push('\n');
+    }
+}
+
+/// Reads the levels of a level set written as text
+pub fn text_to_levels(text: &str) -> Result<Vec<Level>, String> {
+    let mut levels = Vec::new();
+    let mut level_text: Option<LevelText> = None;
+
+    let mut lines = text.lines().enumerate();
+    while let Some((line_index, line)) = lines.next() {
+        let line_number = line_index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("Line {}: unexpected text {}", line_number, line))?;
+        let (key, value) = (key.trim(), value.trim());
+
+        if key == "level" {
+            if let Some(level_text) = level_text.take() {
+                levels.push(level_text.into_level()?);
+            }
+            let level_number = levels.len() + 1;
+            if value.parse::<usize>().ok() != Some(level_number) {
+                return Err(format!(
+                    "Line {}: level {} found instead of level {}",
+                    line_number, value, level_number
+                ));
+            }
+            level_text = Some(LevelText::new(level_number));
+            continue;
+        }
+
+        let level_text = level_text
+            .as_mut()
+            .ok_or_else(|| format!("Line {}: the level number is missing", line_number))?;
+        if key == "tiles" {
+            for row in 0..K_LEVEL_HEIGHT {
+                let row_line = lines.next().map(|(_, row_line)| row_line.trim());
+                level_text
+                    .read_tiles_row(row, row_line)
+                    .map_err(|error| format!("Line {}: {}", line_number + row + 1, error))?;
+            }
+            continue;
+        }
+        level_text
+            .read_field(key, value)
+            .map_err(|error| format!("Line {}: {}", line_number, error))?;
+    }
+
+    if let Some(level_text) = level_text {
+        levels.push(level_text.into_level()?);
+    }
+    Ok(levels)
+}
+
+/// Level being read from text
+struct LevelText {
+    level: Level,
+    special_ports_info: Vec<SpecialPortInfo>,
+    unused_special_ports_info: Vec<SpecialPortInfo>,
+    unknown_tiles: Vec<(usize, u8)>, // Values of the unknown-tile lines
+    unknown_tile_indexes: Vec<usize>, // Tiles written as ?
+    has_tiles: bool,
+}
+
+impl LevelText {
+    fn new(level_number: usize) -> LevelText {
+        let mut level = Level::new();
+        level.number = level_number;
+        LevelText {
+            level,
+            special_ports_info: Vec::new(),
+            unused_special_ports_info: Vec::new(),
+            unknown_tiles: Vec::new(),
+            unknown_tile_indexes: Vec::new(),
+            has_tiles: false,
+        }
+    }
+
+    fn read_field(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let level = &mut self.level;
+        match key {
+            "name" => {
+                let name = unescape_text(value)?;
+                if name.len() > K_LEVEL_NAME_LENGTH - 1 {
+                    return Err(format!(
+                        "the name is longer than {} characters",
+                        K_LEVEL_NAME_LENGTH - 1
+                    ));
+                }
+                level.name = name.iter().map(|&character| character as char).collect();
+            }
+            "gravity" => level.initial_gravitation = parse_flag(value, 1)?,
+            "freeze-zonks" => level.freeze_zonks = parse_flag(value, 2)?,
+            "infotrons-needed" => level.number_of_infotrons = parse_byte(value)?,
+            "special-port" => self.special_ports_info.push(parse_special_port(value)?),
+            "unused-special-port" => self
+                .unused_special_ports_info
+                .push(parse_special_port(value)?),
+            "random-seed" => {
+                level.random_seed =
+                    parse_number(value).ok_or_else(|| format!("invalid random seed {}", value))?
+            }
+            "speed-fix-version" => level.speed_fix_magic_number = parse_byte(value)?,
+            "scrambled-speed" => level.scrambled_speed = parse_byte(value)?,
+            "scrambled-checksum" => level.scrambled_checksum = parse_byte(value)?,
+            "unused" => {
+                let unused = u32::from_str_radix(value, 16)
+                    .ok()
+                    .filter(|_| value.len() == 2 * level.unused.len())
+                    .ok_or_else(|| format!("invalid unused bytes {}", value))?;
+                level.unused = unused.to_be_bytes();
+            }
+            "unknown-tile" => {
+                let (position, tile) = value
+                    .split_once(' ')
+                    .ok_or_else(|| format!("invalid unknown tile {}", value))?;
+                let tile_index = parse_tile_index(position)?;
+                self.unknown_tiles
+                    .push((tile_index, parse_byte(tile.trim())?));
+            }
+            _ => return Err(format!("unknown field {}", key)),
+        }
+        Ok(())
+    }
+
+    fn read_tiles_row(&mut self, row: usize, row_line: Option<&str>) -> Result<(), String> {
+        let row_line = row_line.ok_or("the level has less than 24 rows of tiles")?;
+        let tiles: Vec<char> = row_line.chars().collect();
+        if tiles.len() != K_LEVEL_WIDTH {
+            return Err(format!(
+                "the row has {} tiles instead of {}",
+                tiles.len(),
+                K_LEVEL_WIDTH
+            ));
+        }
+        for (column, &character) in tiles.iter().enumerate() {
+            let tile_index = row * K_LEVEL_WIDTH + column;
+            if character == K_UNKNOWN_TILE_CHARACTER {
+                // Its value is given by an unknown-tile line
+                self.unknown_tile_indexes.push(tile_index);
+                continue;
+            }
+            self.level.tiles[tile_index] = K_LEVEL_TILE_CHARACTERS
+                .iter()
+                .position(|(tile_character, _)| *tile_character == character)
+                .ok_or_else(|| format!("unknown tile {}", character))?
+                as u8;
+        }
+        self.has_tiles = true;
+        Ok(())
+    }
+
+    fn into_level(mut self) -> Result<Level, String> {
+        let level_number = self.level.number;
+        let error = |error: String| format!("Level {}: {}", level_number, error);
+        if !self.has_tiles {
+            return Err(error(String::from("the tiles are missing")));
+        }
+
+        for &(tile_index, tile) in &self.unknown_tiles {
+            if !self.unknown_tile_indexes.contains(&tile_index) {
+                return Err(error(format!(
+                    "the unknown tile at {} is not a ? in the tiles",
+                    tile_index_to_text(tile_index)
+                )));
+            }
+            self.level.tiles[tile_index] = tile;
+        }
+        for &tile_index in &self.unknown_tile_indexes {
+            if !self
+                .unknown_tiles
+                .iter()
+                .any(|&(unknown_tile_index, _)| unknown_tile_index == tile_index)
+            {
+                return Err(error(format!(
+                    "the ? at {} has no unknown-tile line",
+                    tile_index_to_text(tile_index)
+                )));
+            }
+        }
+
+        let number_of_special_ports = self.special_ports_info.len();
+        let special_ports_info = [self.special_ports_info, self.unused_special_ports_info].concat();
+        if special_ports_info.len() > K_LEVEL_MAX_NUMBER_OF_SPECIAL_PORTS {
+            return Err(error(format!(
+                "the level has {} special ports, the maximum is {}",
+                special_ports_info.len(),
+                K_LEVEL_MAX_NUMBER_OF_SPECIAL_PORTS
+            )));
+        }
+        self.level.number_of_special_ports = number_of_special_ports as u8;
+        self.level.special_ports_info[..special_ports_info.len()]
+            .copy_from_slice(&special_ports_info);
+
+        level_codec::validate_level(&self.level).map_err(error)?;
+        Ok(self.level)
+    }
+}
+
+fn is_empty_special_port(special_port_info: &SpecialPortInfo) -> bool {
+    special_port_info.position == 0
+        && special_port_info.gravity == 0
+        && special_port_info.freeze_zonks == 0
+        && special_port_info.freeze_enemies == 0
+        && special_port_info.unused == 0
+}
+
+/// Reads a special port like "12,5 gravity=on freeze-zonks=off freeze-enemies=off"
+fn parse_special_port(value: &str) -> Result<SpecialPortInfo, String> {
+    let mut words = value.split_whitespace();
+    let position = words.next().ok_or("the position of the port is missing")?;
+    let mut special_port_info = SpecialPortInfo {
+        position: if position.starts_with("0x") {
+            parse_number(position).ok_or_else(|| format!("invalid position {}", position))?
+        } else {
+            (parse_tile_index(position)? * 2) as u16
+        },
+        ..Default::default()
+    };
+
+    for word in words {
+        let (key, flag) = word
+            .split_once('=')
+            .ok_or_else(|| format!("unexpected text {}", word))?;
+        match key {
+            "gravity" => special_port_info.gravity = parse_flag(flag, 1)?,
+            "freeze-zonks" => special_port_info.freeze_zonks = parse_flag(flag, 2)?,
+            "freeze-enemies" => special_port_info.freeze_enemies = parse_flag(flag, 1)?,
+            "unused" => special_port_info.unused = parse_byte(flag)?,
+            _ => return Err(format!("unknown special port field {}", key)),
+        }
+    }
+    Ok(special_port_info)
+}
+
+/// Special port positions are written as x,y, or as they are stored when they're not a tile of the
+/// level
+fn special_port_position_to_text(position: u16) -> String {
+    let position = position as usize;
+    if position & 1 == 0 && position / 2 < K_LEVEL_SIZE {
+        tile_index_to_text(position / 2)
+    } else {
+        format!("0x{:04X}", position)
+    }
+}
+
+fn tile_index_to_text(tile_index: usize) -> String {
+    format!(
+        "{},{}",
+        tile_index % K_LEVEL_WIDTH,
+        tile_index / K_LEVEL_WIDTH
+    )
+}
+
+fn parse_tile_index(value: &str) -> Result<usize, String> {
+    value
+        .split_once(',')
+        .and_then(|(x, y)| {
+            Some((
+                x.trim().parse::<usize>().ok()?,
+                y.trim().parse::<usize>().ok()?,
+            ))
+        })
+        .filter(|&(x, y)| x < K_LEVEL_WIDTH && y < K_LEVEL_HEIGHT)
+        .map(|(x, y)| y * K_LEVEL_WIDTH + x)
+        .ok_or_else(|| format!("invalid position {}", value))
+}
+
+fn flag_to_text(value: u8, on_value: u8) -> String {
+    match value {
+        0 => String::from("off"),
+        value if value == on_value => String::from("on"),
+        value => value.to_string(),
+    }
+}
+
+fn parse_flag(value: &str, on_value: u8) -> Result<u8, String> {
+    match value {
+        "on" => Ok(on_value),
+        "off" => Ok(0),
+        value => parse_byte(value),
+    }
+}
+
+fn parse_byte(value: &str) -> Result<u8, String> {
+    parse_number(value)
+        .and_then(|value| u8::try_from(value).ok())
+        .ok_or_else(|| format!("invalid value {}", value))
+}
+
+fn parse_number(value: &str) -> Option<u16> {
+    match value.strip_prefix("0x") {
+        Some(value) => u16::from_str_radix(value, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::level::LevelManager;
+    use std::fs;
+
+    fn read_first_level_set() -> LevelManager {
+        let mut level_manager = LevelManager::new();
+        let levels_dat_path = format!(
+            "{}/{}/LEVELS.DAT",
+            env!("CARGO_MANIFEST_DIR"),
+            RESSOURCES_PATH
+        );
+        level_manager.read_level_set(&levels_dat_path).unwrap();
+        level_manager
+    }
+
+    #[test]
+    fn shipped_level_sets_are_read_back_identically_from_text() {
+        let resources_path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), RESSOURCES_PATH);
+        for entry in fs::read_dir(resources_path).unwrap() {
+            let path = entry.unwrap().path().to_string_lossy().to_string();
+            if !path.contains("LEVELS.D") {
+                continue;
+            }
+            let mut level_manager = LevelManager::new();
+            level_manager.read_level_set(&path).unwrap();
+
+            let levels = text_to_levels(&level_manager.text_data())
+                .unwrap_or_else(|error| panic!("{}: {}", path, error));
+            assert_eq!(levels.len(), level_manager.g_number_of_levels);
+            for (i, level) in levels.iter().enumerate() {
+                assert_eq!(level.number, i + 1);
+                assert_eq!(
+                    level.to_raw(),
+                    level_manager.g_level_list_data[i].to_raw(),
+                    "Level {} of {}",
+                    i + 1,
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn first_shipped_level_is_written_as_text() {
+        let level_manager = read_first_level_set();
+        let text = levels_to_text(&level_manager.g_level_list_data[..1]);
+        assert!(text.contains("\nlevel: 1\nname: \"------- WARM UP -------\"\n"));
+        assert!(text.contains(&format!("\ntiles:\n{}\n", "#".repeat(K_LEVEL_WIDTH))));
+        assert_eq!(
+            text.lines()
+                .filter(|line| line.len() == K_LEVEL_WIDTH)
+                .count(),
+            K_LEVEL_HEIGHT
+        );
+    }
+
+    #[test]
+    fn edited_tiles_are_read() {
+        let level_manager = read_first_level_set();
+        let text = levels_to_text(&level_manager.g_level_list_data[..1]);
+        // The second row starts with hardware and a base
+        let text = text.replacen("\n#+", "\n#I", 1);
+
+        let levels = text_to_levels(&text).unwrap();
+        assert_eq!(
+            levels[0].tiles[K_LEVEL_WIDTH + 1],
+            LevelTileType::Infotron as u8
+        );
+    }
+
+    #[test]
+    fn invalid_texts_are_rejected() {
+        let level_manager = read_first_level_set();
+        let text = levels_to_text(&level_manager.g_level_list_data[..2]);
+
+        let invalid_texts = [
+            text.replacen("level: 2", "level: 3", 1),
+            text.replacen("\n#+", "\n#%", 1),
+            text.replacen("\n#+", "\n#", 1),
+            text.replacen("gravity: off", "gravity: maybe", 1),
+            text.replacen("tiles:", "title:", 1),
+            text.replacen("infotrons-needed: 19", "special-port: 60,0 gravity=on", 1),
+            format!("{}\nlevel: 3\nname: \"EMPTY\"\n", text),
+        ];
+        for invalid_text in invalid_texts {
+            assert!(text_to_levels(&invalid_text).is_err(), "{}", invalid_text);
+        }
+    }
+}
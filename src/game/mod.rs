@@ -16,8 +16,13 @@
 */
 
 pub mod animation;
+mod bugs;
 mod button_borders;
 mod demo;
+pub mod demo_text;
+mod enemies;
+mod explosions;
+mod falling_objects;
 mod game_states;
 pub mod gamecontroller;
 pub mod globals;
@@ -25,17 +30,24 @@ pub mod graphics;
 mod input;
 mod keyboard;
 pub mod level;
+mod level_codec;
+mod level_text;
 mod mouse;
+mod mpx;
+mod murphy;
+mod player_list;
+mod simulation;
 mod sounds;
+mod terminals;
 mod utils;
+pub mod verification;
 pub mod video;
 
 use crate::game::button_borders::{
     K_NUMBER_OF_OPTIONS_MENU_BUTTONS, K_OPTIONS_MENU_BUTTON_DESCRIPTORS,
 };
 use crate::game::graphics::{
-    DestinationSurface, K_FIXED_BITMAP_WIDTH, K_LEVEL_BITMAP_HEIGHT, K_LEVEL_BITMAP_WIDTH,
-    K_LEVEL_EDGE_SIZE, K_TILE_SIZE,
+    DestinationSurface, K_LEVEL_BITMAP_HEIGHT, K_LEVEL_BITMAP_WIDTH, K_LEVEL_EDGE_SIZE, K_TILE_SIZE,
 };
 
 use self::button_borders::{
@@ -43,17 +55,20 @@ use self::button_borders::{
 };
 use self::graphics::{BitmapType, K_PANEL_BITMAP_HEIGHT, K_SCREEN_HEIGHT, K_SCREEN_WIDTH};
 use self::input::Input;
-use self::level::LevelManager;
-use self::sounds::SoundType;
+use self::level::{Level, LevelManager};
+use self::murphy::K_RED_DISKS_DISPLAY_FRAMES;
+use self::simulation::K_GAME_FRAMES_PER_SECOND;
+use self::sounds::{SoundEffect, SoundType};
 use button_borders::{
     ButtonStatus, K_MAIN_MENU_BUTTON_BORDERS, K_MAIN_MENU_BUTTON_DESCRIPTORS,
     K_NUMBER_OF_MAIN_MENU_BUTTONS,
 };
-use demo::DemoManager;
+use demo::{DemoManager, K_DEMO_NOT_SAVED_MESSAGE};
 use game_states::GameStates;
 use globals::*;
 use graphics::{Graphics, PaletteType, K_FULL_SCREEN_FRAMEBUFFER_LENGTH, K_MOVING_BITMAP_WIDTH};
 use keyboard::{Keys, UserInput, K_USER_INPUT_SPACE_AND_DIRECTION_OFFSET};
+use log::error;
 use mouse::{Mouse, MOUSE_BUTTON_LEFT, MOUSE_BUTTON_RIGHT};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
@@ -123,7 +138,6 @@ pub struct Game<'a> {
     video: Rc<RefCell<Video<'a>>>,
     events: EventPump,
     sdl_context: Rc<RefCell<sdl2::Sdl>>,
-    g_random_generator_seed: u16,
     g_player_list_data: Box<[Box<PlayerEntry>; K_NUMBER_OF_PLAYERS]>,
     g_hall_of_fame_data: Box<[Box<HallOfFameEntry>; K_NUMBER_OF_HALL_OF_FAME_ENTRIES]>,
     g_is_game_busy: bool,
@@ -132,11 +146,9 @@ pub struct Game<'a> {
     demo_manager: Box<DemoManager>,
     level_manager: Box<LevelManager>,
     states: GameStates,
-    g_has_user_cheated: bool,
     g_should_autoselect_next_level_to_play: bool,
     g_is_forced_level: u8,
     g_is_forced_cheat_mode: bool,
-    g_is_playing_demo: bool,
     g_should_start_from_saved_snapshot: bool,
     word_58467: bool,
     byte_5A19B: bool,
@@ -151,7 +163,6 @@ pub struct Game<'a> {
     byte_59B83: bool,
     byte_50919: u8,
     should_quit_option_menu: bool, // word_58463 in open-supaplex
-    g_level_failed: bool,
     button_states: ButtonStatus,
     keyboard: Rc<RefCell<Keys>>,
     input: Input,
@@ -164,6 +175,7 @@ pub struct Game<'a> {
     g_ranking_list_throttle_next_counter: u16,
     g_level_set_rotation_throttle_current_counter: u16,
     g_level_set_rotation_throttle_next_counter: u16,
+    g_should_show_demo_overlay: bool,
 }
 
 impl Game<'_> {
@@ -183,8 +195,7 @@ impl Game<'_> {
             graphics: Graphics::init(video.clone(), sdl_context.clone()),
             events: events,
             sdl_context: sdl_context,
-            g_random_generator_seed: 0,
-            g_player_list_data: Box::new([(); K_NUMBER_OF_PLAYERS].map(|_|Box::new( PlayerEntry::new()))),
+            g_player_list_data: Box::new([(); K_NUMBER_OF_PLAYERS].map(|_|Box::new( PlayerEntry::new(K_NUMBER_OF_LEVELS)))),
             g_hall_of_fame_data: Box::new([(); K_NUMBER_OF_HALL_OF_FAME_ENTRIES]
                 .map(|_| Box::new(HallOfFameEntry::new()))),
             g_is_game_busy: false,
@@ -193,11 +204,9 @@ impl Game<'_> {
             demo_manager: Box::new(DemoManager::new()),
             level_manager: Box::new(LevelManager::new()),
             states: GameStates::new(),
-            g_has_user_cheated: false,
             g_should_autoselect_next_level_to_play: false,
             g_is_forced_level: 0,
             g_is_forced_cheat_mode: false,
-            g_is_playing_demo: false,
             g_should_start_from_saved_snapshot: false,
             word_58467: true,
             byte_5A19B: false,
@@ -212,7 +221,6 @@ impl Game<'_> {
             byte_58D47: 0,
             current_ranking_index: 0,
             byte_59B83: false,
-            g_level_failed: false,
             button_states: ButtonStatus::default(),
             keyboard: keyboard,
             input: input,
@@ -224,10 +232,21 @@ impl Game<'_> {
             g_ranking_list_throttle_next_counter: 0,
             g_level_set_rotation_throttle_current_counter: 0,
             g_level_set_rotation_throttle_next_counter: 0,
+            g_should_show_demo_overlay: false,
             mouse: Mouse::default(),
         }
     }
 
+    /// Runs the given .SP file, a level or a demo, instead of starting with the main menu
+    pub fn run_sp_file(&mut self, filename: &str) -> Result<(), String> {
+        self.demo_manager.select_sp_file(filename)
+    }
+
+    /// Shows the demo inputs over the game panel while demos are played, to debug desyncs
+    pub fn show_demo_overlay(&mut self) {
+        self.g_should_show_demo_overlay = true;
+    }
+
     pub fn start(&mut self) {
         // Based from open-supaplex
         // parseCommandLineOptions(argc, argv); --> Not used yet
@@ -293,61 +312,58 @@ impl Game<'_> {
         let mut should_quit_the_game = false;
         let mut level_number_forced_to_load = 0_u8;
         while !should_quit_the_game {
-            self.demo_manager.prepare_demo_recording_filename();
+            self.prepare_demo_recording_filename();
             let mut level_number_forced_to_load = 0_u8;
 
             if self.demo_manager.g_is_sp_demo_available_to_run == 2 {
                 self.demo_manager.g_is_sp_demo_available_to_run = 1;
                 if self.demo_manager.file_is_demo {
-                    self.play_demo(0);
+                    self.play_demo(0, true);
                 } else {
-                    self.demo_manager.g_is_playing_demo = false;
+                    self.states.g_is_playing_demo = false;
                 }
                 self.demo_manager.g_selected_original_demo_level_number = 0;
-                self.g_has_user_cheated = true;
+                self.states.g_has_user_cheated = true;
                 self.demo_manager
                     .g_sp_demo_filename
                     .replace_range(3..6, "---");
 
                 self.start_directly_from_level(1);
-                continue;
             } else {
                 level_number_forced_to_load = self.g_is_forced_level;
                 self.g_is_forced_level = 0;
-                self.g_is_playing_demo = false;
+                self.states.g_is_playing_demo = false;
                 if level_number_forced_to_load > 0 {
                     self.convert_level_number_to_3_digit_string_with_padding_0(
-                        level_number_forced_to_load,
+                        level_number_forced_to_load.into(),
                     );
                 }
-            }
 
-            if level_number_forced_to_load > 0 {
-                self.start_directly_from_level(level_number_forced_to_load);
-                continue;
-            }
-            if self.g_should_start_from_saved_snapshot {
-                self.start_directly_from_level(1);
-                continue;
+                if level_number_forced_to_load > 0 {
+                    self.start_directly_from_level(level_number_forced_to_load.into());
+                } else if self.g_should_start_from_saved_snapshot {
+                    self.start_directly_from_level(1);
+                } else {
+                    self.states.g_has_user_cheated = false;
+                    self.run_main_menu();
+                }
             }
 
-            self.g_has_user_cheated = false;
-            self.run_main_menu();
-
             if should_quit_the_game {
                 break;
             }
-            /* TODO later since only in second cycle */
-            // self.read_levels(); already done in read_level_lst
+            // When the .SP file given on the command line can't be read anymore, the game goes back
+            // to the main menu
+            if let Err(error) = self.read_levels() {
+                error!("{}", error);
+                self.demo_manager.g_is_sp_demo_available_to_run = 0;
+                self.states.g_is_playing_demo = false;
+                continue;
+            }
             self.graphics.fade_to_palette(PaletteType::Black);
             self.g_is_game_busy = false;
             self.draw_player_list();
-            self.initialize_game_info();
-            self.draw_fixed_level();
-            self.draw_game_panel(); // 01ED:0311
-            let number_of_infotrons: u16 = self.convert_to_easy_tiles();
-            self.reset_number_of_infotrons(number_of_infotrons);
-            self.find_murphy();
+            self.initialize_level();
             self.states.g_current_panel_height = K_PANEL_BITMAP_HEIGHT;
             self.graphics
                 .draw_current_level_viewport(self.states.g_current_panel_height); // Added by open-supaplex
@@ -404,201 +420,318 @@ impl Game<'_> {
     }
 
     fn run_level(&mut self) {
-        /*
-            if (gIsPlayingDemo == 0)
-            {
-                gIsLevelStartedAsDemo = 0;
-                gLevelFailed = 1;
-            }
-            else
-            {
-                gIsLevelStartedAsDemo = 1;
-                gLevelFailed = 0;
-            }
-
-            if (gDemoRecordingJustStarted == 1)
-            {
-                gDemoRecordingJustStarted = 0;
-                drawGameTime();
-
-                do
-                {
-                    int9handler(1);
-                }
-                while (areAnyF1ToF10KeysPressed());
+        if self.states.g_is_playing_demo {
+            self.states.g_is_level_started_as_demo = true;
+            self.states.g_level_failed = false;
+        } else {
+            self.states.g_is_level_started_as_demo = false;
+            self.states.g_level_failed = true;
+        }
 
-                initializeGameInfo();
-                if (isMusicEnabled == 0)
-                {
-                    stopMusic();
-                }
+        self.states.g_planted_red_disk_countdown = 0;
 
-                gIsLevelStartedAsDemo = 0;
-                gLevelFailed = 1;
-            }
+        loop {
+            self.handle_game_iteration_started();
 
-            gPlantedRedDiskCountdown = 0;
-            byte_5A323 = 0;
+            self.update_keyboard_state();
 
-            do
-            {
-                handleGameIterationStarted();
+            self.handle_game_user_input(); // 01ED:1F08
 
-                int9handler(0);
+            self.update_user_input();
+            let user_input = if self.states.g_is_playing_demo {
+                self.next_demo_input()
+            } else {
+                self.keyboard.borrow().g_current_user_input
+            };
+            if self.demo_manager.g_is_recording_demo {
+                self.demo_manager.record_demo_input(user_input);
+            }
+            let random_number = self.states.update_level(user_input); // 01ED:1F28
+            self.play_pending_sound_effects();
 
-                uint16_t mouseButtonsStatus;
+            self.draw_number_of_remaining_infotrons();
+            self.draw_number_of_remaining_red_disks();
+            self.draw_game_time();
 
-                getMouseStatus(NULL, NULL, &mouseButtonsStatus);
+            self.update_scroll_offset(random_number);
 
-                if (gIsDebugModeEnabled != 0)
-                {
-                    if (gToggleFancyEasyTilesThrottleCounter != 0)
-                    {
-                        gToggleFancyEasyTilesThrottleCounter--;
-                    }
+            self.graphics.draw_level_state(&self.states);
+            self.graphics
+                .draw_current_level_viewport(self.states.g_current_panel_height); // Added by open-supaplex
+            if self.g_should_show_demo_overlay && self.states.g_is_playing_demo {
+                self.draw_demo_overlay();
+            }
+            if self.graphics.fast_mode != FastModeType::Ultra {
+                self.graphics.video_loop(); // 01ED:2142
+            }
+            self.handle_game_iteration_finished();
 
-                    if (gIsEnterPressed == 0
-                        && mouseButtonsStatus == MouseButtonLeft //cmp bx, 1
-                        && gToggleFancyEasyTilesThrottleCounter == 0)
-                    {
-                        gToggleFancyEasyTilesThrottleCounter = 0xA;
-                        restoreOriginalFancyTiles(); // 01ED:1EFF
-                        drawFixedLevel();
-                        convertToEasyTiles();
-                    }
+            for event in self.events.poll_iter() {
+                if let Event::Quit { .. } = event {
+                    self.g_should_exit_game = true;
                 }
+            }
 
-                handleGameUserInput(); // 01ED:1F08
-                if (gDemoRecordingJustStarted == 1)
-                {
-                    // Restart the demo
-                    gDemoRecordingJustStarted = 0;
-                    drawGameTime();
+            if self.g_should_exit_game {
+                break;
+            }
+            if self.states.finish_frame() {
+                break;
+            }
+        }
 
-                    do
-                    {
-        //isFunctionKey:
-                        int9handler(1);
-                    }
-                    while (areAnyF1ToF10KeysPressed());
+        if self.demo_manager.g_is_recording_demo {
+            if let Err(error) = self.demo_manager.stop_demo_recording() {
+                error!("{}", error);
+            }
+        }
+        self.demo_manager.g_has_demo_recording_failed = false;
 
-        //notFunctionKey:
-                    initializeGameInfo();
-                    if (isMusicEnabled == 0)
-                    {
-                        stopMusic();
-                    }
+        if !self.states.g_has_user_cheated && self.states.g_should_update_total_level_time {
+            self.add_current_game_time_to_player();
+        }
 
-                    gIsLevelStartedAsDemo = 0;
-                    gLevelFailed = 1;
+        // Murphy marks the level as completed when he reaches the exit, it stays not completed when
+        // he dies
+        self.byte_5A19B = self.states.g_current_player_level_state == PlayerLevelState::Completed;
+        if self.byte_5A19B || self.states.g_level_failed {
+            self.change_player_current_level_state();
+        }
+        self.states.g_current_player_level_state = PlayerLevelState::NotCompleted;
+        self.states.g_has_user_cheated = false;
 
-                    gPlantedRedDiskCountdown = 0;
-                    byte_5A323 = 0;
+        self.states.g_is_move_scroll_mode_enabled = false;
+        self.graphics.g_additional_scroll_offset_x = 0;
+        self.graphics.g_additional_scroll_offset_y = 0;
+    }
 
-                    continue;
+    /// Initializes the level loaded in the level state and draws it
+    fn initialize_level(&mut self) {
+        self.states.initialize_game_info();
+        self.draw_fixed_level();
+        self.draw_game_panel(); // 01ED:0311
+        let number_of_infotrons: u16 = self.states.convert_to_easy_tiles();
+        self.states.reset_number_of_infotrons(number_of_infotrons);
+        self.draw_number_of_remaining_infotrons();
+        self.states.find_murphy();
+        self.scroll_to_murphy();
+    }
 
-                    // All the code in this "if" is equivalent to "jmp loc_48ADF"
+    /// Restarts the current level with a new random seed, and records the user inputs until the
+    /// level ends or the recording is stopped (recordDemo)
+    fn start_demo_recording(&mut self, demo_index: u8) {
+        let level_number = self.states.g_current_selected_level_index;
+        let level_data = if self.demo_manager.g_is_sp_demo_available_to_run == 1 {
+            match self.demo_manager.read_sp_level_raw_data() {
+                Ok(level_data) => level_data,
+                Err(error) => {
+                    error!("{}", error);
+                    return;
                 }
+            }
+        } else {
+            self.level_manager.g_level_list_data[level_number as usize - 1].to_raw()
+        };
+        self.generate_random_seed_from_clock();
+        self.demo_manager.start_demo_recording(
+            demo_index,
+            level_number,
+            level_data,
+            self.states.g_random_generator_seed,
+        );
 
-                if (gIsFlashingBackgroundModeEnabled != 0)
-                {
-                    replaceCurrentPaletteColor(0, (Color) { 0x35, 0x35, 0x35 });
-                }
+        if let Err(error) = self.read_levels() {
+            error!("{}", error);
+        }
+        self.initialize_level();
+        self.states.g_is_level_started_as_demo = false;
+        self.states.g_level_failed = true;
+    }
 
-        //noFlashing:
-                updateMovingObjects(); // 01ED:1F28
-                if (gIsFlashingBackgroundModeEnabled != 0)
-                {
-                    replaceCurrentPaletteColor(0, (Color) { 0x21, 0x21, 0x21 });
-                }
+    fn add_current_game_time_to_player(&mut self) {
+        if !self.states.g_should_update_total_level_time {
+            return;
+        }
+        self.states.g_should_update_total_level_time = false;
 
-        //noFlashing2:
-                drawGameTime();
-                clearAdditionalInfoInGamePanelIfNeeded();
-                if (gIsFlashingBackgroundModeEnabled != 0)
-                {
-                    replaceCurrentPaletteColor(0, (Color) { 0x2d, 0x21, 0x0f });
-                }
+        let current_player_entry = &mut self.g_player_list_data[self.states.g_current_player_index];
 
-                updatePlantedRedDisk();
-                updateExplosionTimers();
-                updateScrollOffset();
+        let mut seconds = current_player_entry.seconds as u32 + self.states.g_game_seconds as u32;
+        let mut minutes = current_player_entry.minutes as u32 + self.states.g_game_minutes as u32;
+        let mut hours = current_player_entry.hours as u32 + self.states.g_game_hours as u32;
+        minutes += seconds / 60;
+        seconds %= 60;
+        hours += minutes / 60;
+        minutes %= 60;
 
-                if (gIsFlashingBackgroundModeEnabled != 0)
-                {
-                    replaceCurrentPaletteColor(0, (Color) { 0x3f, 0x3f, 0x3f });
-                }
+        // The total time stops at 255:59:59
+        if hours > u8::MAX as u32 {
+            (hours, minutes, seconds) = (u8::MAX as u32, 59, 59);
+        }
 
-                drawCurrentLevelViewport(gCurrentPanelHeight); // Added by open-supaplex
-                if (gFastMode != FastModeTypeUltra)
-                {
-                    videoLoop(); // 01ED:2142
-                }
-                handleGameIterationFinished();
+        current_player_entry.hours = hours as u8;
+        current_player_entry.minutes = minutes as u8;
+        current_player_entry.seconds = seconds as u8;
+    }
 
-                if (gDebugExtraRenderDelay > 1)
-                {
-                    playBaseSound();
+    /// Reads the user input of the next frame from the demo being played. The level ends with the
+    /// demo, unless Murphy is already leaving it.
+    fn next_demo_input(&mut self) -> UserInput {
+        match self.demo_manager.next_demo_input() {
+            Some(user_input) => user_input,
+            None => {
+                if self.states.g_quit_level_countdown == 0 {
+                    self.states.g_should_exit_level = true;
                 }
+                UserInput::None
+            }
+        }
+    }
 
-                // Extra delays in debug mode
-                for (int i = 1; i < gDebugExtraRenderDelay; ++i)
-                {
-                    if (gFastMode == FastModeTypeNone)
-                    {
-                        videoLoop(); // 01ED:2160
-                    }
+    /// Draws the input of the demo being played over the game panel: the input, the number of frames
+    /// it is still repeated, the demo byte it comes from and the frame counter
+    fn draw_demo_overlay(&mut self) {
+        let input_name = format!("{:?}", self.demo_manager.g_demo_current_input).to_uppercase();
+        let demo_byte_number =
+            self.demo_manager.g_demo_current_input_index - self.demo_manager.word_5A33C;
+        let overlay_text = format!(
+            "{:<10} +{:<2} BYTE {:>4}/{:<4} FRAME {:>5}",
+            input_name,
+            self.demo_manager.g_demo_current_input_repeater_count - 1,
+            demo_byte_number,
+            self.demo_manager.number_of_demo_inputs(),
+            self.states.g_frame_counter
+        );
+        self.graphics
+            .draw_text_with_chars6_font_with_opaque_background(
+                0,
+                K_SCREEN_HEIGHT - K_PANEL_BITMAP_HEIGHT,
+                6,
+                overlay_text,
+            );
+    }
 
-                    handleGameUserInput();
-                }
+    /// Plays the sound effects queued by the simulation during the last frame
+    fn play_pending_sound_effects(&mut self) {
+        let sound_effects = std::mem::take(&mut self.states.g_pending_sound_effects);
+        for sound_effect in sound_effects {
+            match sound_effect {
+                SoundEffect::Explosion => self.sounds.play_explosion_sound(),
+                SoundEffect::Infotron => self.sounds.play_infotron_sound(),
+                SoundEffect::Push => self.sounds.play_push_sound(),
+                SoundEffect::Fall => self.sounds.play_fall_sound(),
+                SoundEffect::Bug => self.sounds.play_bug_sound(),
+                SoundEffect::Base => self.sounds.play_base_sound(),
+                SoundEffect::Exit => self.sounds.play_exit_sound(),
+                SoundEffect::Count => {}
+            }
+        }
+    }
 
-                if (gIsFlashingBackgroundModeEnabled != 0)
-                {
-                    replaceCurrentPaletteColor(0, (Color) { 0, 0, 0 });
-                }
+    fn handle_game_iteration_started(&mut self) {
+        self.graphics.start_tracking_tender_delta_time();
+    }
 
-                if (gShouldExitGame != 0)
-                {
-                    break;
-                }
-                gFrameCounter++;
-                if (gShouldExitLevel == 1)
-                {
-                    break;
-                }
-                if (gQuitLevelCountdown == 0) // 01ED:218D
-                {
-                    continue;
-                }
+    fn handle_game_iteration_finished(&mut self) {
+        if self.graphics.fast_mode != FastModeType::None {
+            return;
+        }
 
-                gQuitLevelCountdown--;
-                if (gQuitLevelCountdown == 0)
-                {
-                    break;
-                }
+        // Keeps the game running at the speed of the original game
+        const K_GAME_ITERATION_DURATION: u32 = 1000 / K_GAME_FRAMES_PER_SECOND as u32;
+        let duration = self.graphics.update_render_delta_time();
+        if duration < K_GAME_ITERATION_DURATION {
+            sleep(Duration::from_millis(
+                (K_GAME_ITERATION_DURATION - duration) as u64,
+            ));
+        }
+    }
+
+    fn handle_game_user_input(&mut self) {
+        if self.keyboard.borrow().g_is_escape_key_pressed
+            || self.input.is_exit_level_button_pressed()
+        {
+            if self.states.g_is_playing_demo {
+                self.states.g_should_exit_level = true;
+            } else if self.states.g_quit_level_countdown == 0 {
+                self.states.g_should_kill_murphy = true;
             }
-            while(1);
+        }
 
-            if (gIsRecordingDemo != 0)
-            {
-                stopRecordingDemo();
+        if self.input.is_toggle_game_panel_button_pressed() {
+            if self.states.g_toggle_game_panel_key_auto_repeat_counter == 0 {
+                self.states.g_should_show_game_panel = !self.states.g_should_show_game_panel;
+                self.states.g_current_panel_height = if self.states.g_should_show_game_panel {
+                    K_PANEL_BITMAP_HEIGHT
+                } else {
+                    0
+                };
             }
+            self.states.g_toggle_game_panel_key_auto_repeat_counter = 1;
+        } else {
+            self.states.g_toggle_game_panel_key_auto_repeat_counter = 0;
+        }
 
-            uint8_t userDidNotCheat = (gHasUserCheated == 0);
-            gHasUserCheated = 0;
-            if (userDidNotCheat
-                && gShouldUpdateTotalLevelTime != 0
-                && byte_5A323 == 0)
-            {
-                addCurrentGameTimeToPlayer();
+        if self.input.is_show_number_of_red_disks_button_pressed() {
+            self.states.g_additional_info_in_game_panel_frame_counter = K_RED_DISKS_DISPLAY_FRAMES;
+        }
+
+        if self.demo_manager.g_is_recording_demo {
+            let keyboard = self.keyboard.borrow();
+            let should_stop_recording =
+                keyboard.g_is_left_control_key_pressed && keyboard.g_is_f12_key_pressed;
+            drop(keyboard);
+            if should_stop_recording {
+                if let Err(error) = self.demo_manager.stop_demo_recording() {
+                    error!("{}", error);
+                }
+                self.draw_game_panel_text();
             }
+        } else if !self.states.g_is_playing_demo {
+            let keyboard = self.keyboard.borrow();
+            let demo_index = [
+                keyboard.g_is_f1_key_pressed,
+                keyboard.g_is_f2_key_pressed,
+                keyboard.g_is_f3_key_pressed,
+                keyboard.g_is_f4_key_pressed,
+                keyboard.g_is_f5_key_pressed,
+                keyboard.g_is_f6_key_pressed,
+                keyboard.g_is_f7_key_pressed,
+                keyboard.g_is_f8_key_pressed,
+                keyboard.g_is_f9_key_pressed,
+                keyboard.g_is_f10_key_pressed,
+            ]
+            .iter()
+            .position(|&is_pressed| is_pressed);
+            drop(keyboard);
+            if let Some(demo_index) = demo_index {
+                self.start_demo_recording(demo_index as u8);
+            }
+        }
+    }
 
-            gIsMoveScrollModeEnabled = 0;
-            gAdditionalScrollOffsetX = 0;
-            gAdditionalScrollOffsetY = 0;
-            gIsFlashingBackgroundModeEnabled = 0;
-            gDebugExtraRenderDelay = 1;
-            replaceCurrentPaletteColor(0, (Color) { 0, 0, 0 }); */
+    /// Loads the level to play into the level state. Demos either include their level, or play one
+    /// of the level list. Fails when the .SP file being run can't be read anymore.
+    fn read_levels(&mut self) -> Result<(), String> {
+        let level = if self.demo_manager.g_is_sp_demo_available_to_run == 1
+            && !self.states.g_is_playing_demo
+        {
+            Level::from_raw(0, &self.demo_manager.read_sp_level_raw_data()?).map_err(|error| {
+                format!("{}: {}", self.demo_manager.demo_file_name, error)
+            })?
+        } else if self.states.g_is_playing_demo && !self.demo_manager.is_original_demo() {
+            let demo_index = self.demo_manager.g_demo_index_or_demo_level_number as usize;
+            self.demo_manager.g_demos.level[demo_index].clone()
+        } else {
+            let level_number = if self.states.g_is_playing_demo {
+                self.demo_manager.g_demo_index_or_demo_level_number as usize
+            } else {
+                self.states.g_current_selected_level_index as usize
+            };
+            self.level_manager.g_level_list_data[level_number - 1].clone()
+        };
+        self.states.load_level(&level);
+        Ok(())
     }
 
     fn wait_for_key_press_or_mouse_click(&mut self) {
@@ -637,40 +770,98 @@ impl Game<'_> {
         }
     }
 
-    fn play_demo(&mut self, demo_index: u16) {
+    /// Plays the demo with the given index. The signature of the demo is shown first when the player
+    /// asked for it, and not when the main menu plays demos on its own.
+    fn play_demo(&mut self, demo_index: u16, should_show_signature: bool) {
         self.demo_manager.read_demo_files();
 
-        self.g_random_generator_seed = self.demo_manager.g_demo_random_seeds[demo_index as usize];
+        self.states.g_random_generator_seed =
+            self.demo_manager.g_demo_random_seeds[demo_index as usize];
         self.states.g_should_leave_main_menu = true;
         self.states.g_is_playing_demo = true;
+        self.states.g_should_update_total_level_time = false;
 
-        let mut demo_first_index: u16 =
-            self.demo_manager.g_demos.demo_first_indices[demo_index as usize];
-
-        if demo_first_index == 0xffff {
+        if !self
+            .demo_manager
+            .prepare_demo_playback(demo_index, self.level_manager.g_number_of_levels)
+        {
             self.states.g_should_leave_main_menu = false;
             self.states.g_is_playing_demo = false;
+            return;
         }
 
-        self.demo_manager.g_selected_original_demo_level_number = 0;
+        if should_show_signature {
+            self.draw_demo_signature_screen(demo_index as usize);
+        }
+    }
+
+    /// Shows the signature of the demo about to be played, to give credit to its author
+    fn draw_demo_signature_screen(&mut self, demo_index: usize) {
+        // Width of the screen in characters of the 6px font
+        const K_MAX_SIGNATURE_LINE_LENGTH: usize = K_SCREEN_WIDTH / 6;
+        const K_MAX_NUMBER_OF_SIGNATURE_LINES: usize = 16;
 
-        let demo_level_number =
-            self.demo_manager.g_demos.demo_data[demo_first_index as usize] as u16;
-        let mut final_level_number = demo_index;
+        let signature = self.demo_manager.g_demos.signatures[demo_index].clone();
+        if signature.trim().is_empty() || self.graphics.fast_mode != FastModeType::None {
+            return;
+        }
 
-        if demo_level_number <= K_NUMBER_OF_LEVELS as u16 && demo_level_number != 0 {
-            final_level_number = demo_level_number;
-            self.demo_manager.g_selected_original_demo_level_number =
-                (self.demo_manager.g_selected_original_demo_level_number & 0xFF00)
-                    | final_level_number as usize;
+        // The font only has uppercase characters
+        let mut lines = Vec::new();
+        for line in signature.to_uppercase().lines() {
+            let line: Vec<char> = line
+                .trim_end()
+                .chars()
+                .map(|character| match character {
+                    ' '..='_' => character,
+                    _ => ' ',
+                })
+                .collect();
+            if line.is_empty() {
+                lines.push(String::new());
+            }
+            for chunk in line.chunks(K_MAX_SIGNATURE_LINE_LENGTH) {
+                lines.push(chunk.iter().collect::<String>());
+            }
         }
-        self.demo_manager.g_demo_index_or_demo_level_number = final_level_number;
 
-        demo_first_index += 1; // To skip the level number
-        self.demo_manager.g_demo_current_input_index = demo_first_index;
-        self.demo_manager.word_5A33C = demo_first_index;
-        self.demo_manager.g_demo_current_input = UserInput::None;
-        self.demo_manager.g_demo_current_input_repeater_count = 1;
+        self.graphics.fade_to_palette(PaletteType::Black);
+
+        let screen_pixel_backup = self.video.borrow_mut().get_screen_pixels();
+
+        self.graphics.draw_back_background();
+
+        self.draw_text_with_chars6_font_with_transparent_background_if_possible(
+            118,
+            10,
+            15,
+            String::from("DEMO SIGNATURE"),
+        );
+        for (i, line) in lines
+            .iter()
+            .take(K_MAX_NUMBER_OF_SIGNATURE_LINES)
+            .enumerate()
+        {
+            let line = line.trim_start();
+            self.draw_text_with_chars6_font_with_transparent_background_if_possible(
+                (K_SCREEN_WIDTH - line.len() * 6) / 2,
+                30 + i * 10,
+                15,
+                line.to_string(),
+            );
+        }
+
+        self.graphics
+            .fade_to_palette(PaletteType::InformationScreenPalette);
+        self.wait_for_key_press_or_mouse_click();
+
+        self.graphics.fade_to_palette(PaletteType::Black);
+
+        self.video
+            .borrow_mut()
+            .set_screen_pixels(screen_pixel_backup);
+
+        self.graphics.fade_to_palette(PaletteType::GamePalette);
     }
 
     fn load_all_ressources(&mut self) {
@@ -718,6 +909,12 @@ impl Game<'_> {
 
     /// Read the PLAYER.DAT file to load previous player save.
     fn read_players_lst(&mut self) {
+        // Players missing from the file are left empty, with a state for each level of the set
+        let number_of_levels = self.level_manager.g_number_of_levels;
+        for player in self.g_player_list_data.iter_mut() {
+            **player = PlayerEntry::new(number_of_levels);
+        }
+
         let path = format!("{}/{}", RESSOURCES_PATH, G_PLAYERS_LST_FILENAME);
         let player_lst_file_path = Path::new(&path);
         match player_lst_file_path
@@ -727,17 +924,17 @@ impl Game<'_> {
             true => (),
             false => return, // No player file found
         }
-        let mut file = File::open(player_lst_file_path)
+        let player_list_data = std::fs::read(player_lst_file_path)
             .expect(format!("Error while opening {}", G_PLAYERS_LST_FILENAME).as_str());
 
-        let mut player_data: [u8; K_PLAYER_ENTRY_SIZE] = [0; K_PLAYER_ENTRY_SIZE];
-        for i in 0..K_NUMBER_OF_PLAYERS {
-            match file.read_exact(&mut player_data) {
-                Ok(_) => (),
-                Err(_) => return, // No more players to load
+        // A corrupt player list is ignored, the players start over like without any file
+        match player_list::read_player_list(&player_list_data, number_of_levels) {
+            Ok(players) => {
+                for (i, player) in players.into_iter().enumerate() {
+                    *self.g_player_list_data[i] = player;
+                }
             }
-
-            self.g_player_list_data[i] = Box::new(PlayerEntry::from(player_data));
+            Err(error) => error!("{}: {}", G_PLAYERS_LST_FILENAME, error),
         }
     }
 
@@ -929,22 +1126,14 @@ impl Game<'_> {
         let clock_count: u32 = time_in_milliseconds * 182 / 10000;
         let low_value: u16 = (clock_count & 0xffff) as u16;
         let high_value = ((clock_count >> 16) & 0xfff) as u16;
-        self.g_random_generator_seed = high_value ^ low_value;
-    }
-
-    fn generate_random_number(&mut self) -> u16 {
-        let mut some_value = self.g_random_generator_seed;
-        some_value *= 0x5E5; // 1509
-        some_value += 0x31; // '1' or 49
-        self.g_random_generator_seed = some_value;
-        some_value / 2
+        self.states.g_random_generator_seed = high_value ^ low_value;
     }
 
     fn initialize_fade_palette(&mut self) {
         self.graphics.set_palette(PaletteType::Black);
     }
 
-    fn start_directly_from_level(&mut self, level_number: u8) {
+    fn start_directly_from_level(&mut self, level_number: u16) {
         self.g_is_game_busy = true;
         self.g_should_autoselect_next_level_to_play = true;
         self.prepare_level_data_for_current_player();
@@ -959,22 +1148,22 @@ impl Game<'_> {
     }
 
     fn prepare_level_data_for_current_player(&mut self) {
+        let number_of_levels = self.level_manager.g_number_of_levels;
+        let last_level_index = self.level_manager.last_level_index() as u16;
         let mut current_player_entry =
             &mut (self.g_player_list_data[self.states.g_current_player_index]);
 
-        let current_player_level_state = current_player_entry.level_state;
-
         // Sets everything to 6 which seems to mean all levels are blocked
         self.states.g_current_player_padded_level_data =
-            Box::new([K_SKIPPED_LEVEL_ENTRY_COLOR; K_NUMBER_OF_LEVEL_WITH_PADDING]);
-        for i in 0..K_NUMBER_OF_LEVELS {
+            vec![K_SKIPPED_LEVEL_ENTRY_COLOR; number_of_levels + K_LEVEL_LIST_PADDING];
+        for i in 0..number_of_levels {
             self.states
                 .set_g_current_player_level_data(i, K_BLOCKED_LEVEL_ENTRY_COLOR);
         }
 
         let mut is_first_uncompleted_level = true;
 
-        for i in 0..K_NUMBER_OF_LEVELS {
+        for i in 0..number_of_levels {
             if current_player_entry.level_state[i] == PlayerLevelState::Skipped {
                 self.states
                     .set_g_current_player_level_data(i, K_SKIPPED_LEVEL_ENTRY_COLOR);
@@ -1000,10 +1189,10 @@ impl Game<'_> {
         }
 
         let mut has_completed_all_levels: bool = true;
-        let mut next_level_to_play: u8 = 1;
+        let mut next_level_to_play: u16 = 1;
 
         // Looks for the first uncompleted level
-        for i in 0..K_NUMBER_OF_LEVELS {
+        for i in 0..number_of_levels {
             if current_player_entry.level_state[i] == PlayerLevelState::NotCompleted
             // not completed
             {
@@ -1018,7 +1207,7 @@ impl Game<'_> {
             next_level_to_play = 1;
 
             // Looks for the first completed level
-            for i in 0..K_NUMBER_OF_LEVELS {
+            for i in 0..number_of_levels {
                 if current_player_entry.level_state[i] == PlayerLevelState::Skipped {
                     has_completed_all_levels = false;
                     break;
@@ -1029,10 +1218,10 @@ impl Game<'_> {
 
         if has_completed_all_levels {
             if self.g_should_autoselect_next_level_to_play {
-                self.states.g_current_selected_level_index = K_LAST_LEVEL_INDEX as u8;
+                self.states.g_current_selected_level_index = last_level_index;
             }
 
-            current_player_entry.next_level_to_play = K_LAST_LEVEL_INDEX as u8;
+            current_player_entry.next_level_to_play = last_level_index;
             return;
         }
 
@@ -1111,9 +1300,9 @@ impl Game<'_> {
             .draw_text_with_chars6_font_with_opaque_background(288, 93, 8, next_level_text);
     }
 
-    fn convert_level_number_to_3_digit_string_with_padding_0(&mut self, value: u8) {
+    fn convert_level_number_to_3_digit_string_with_padding_0(&mut self, value: u16) {
         log::debug!("convert_level_number_to_3_digit_string_with_padding_0. previous filename = {}, value = {}", self.demo_manager.g_sp_demo_filename, value);
-        let s = format!("{:03}", value.to_string());
+        let s = format!("{:03}", value);
         self.demo_manager
             .g_sp_demo_filename
             .replace_range(3..6, s.as_str());
@@ -1132,8 +1321,7 @@ impl Game<'_> {
             0 | 1 => String::from(" ").repeat(27),
             _ => self.level_manager.g_level_list_data
                 [self.states.g_current_selected_level_index as usize - 2]
-                .name
-                .clone(),
+                .list_name(),
         };
         self.draw_text_with_chars6_font_with_opaque_background_if_possible(
             144,
@@ -1146,8 +1334,7 @@ impl Game<'_> {
             0 => String::from(" ").repeat(27),
             _ => self.level_manager.g_level_list_data
                 [self.states.g_current_selected_level_index as usize - 1]
-                .name
-                .clone(),
+                .list_name(),
         };
         self.draw_text_with_chars6_font_with_opaque_background_if_possible(
             144,
@@ -1158,8 +1345,7 @@ impl Game<'_> {
 
         let next_level_name = self.level_manager.g_level_list_data
             [self.states.g_current_selected_level_index as usize]
-            .name
-            .clone();
+            .list_name();
         self.draw_text_with_chars6_font_with_opaque_background_if_possible(
             144,
             173,
@@ -1197,7 +1383,7 @@ impl Game<'_> {
             (self.g_automatic_demo_playback_countdown, _) =
                 self.g_automatic_demo_playback_countdown.overflowing_sub(1);
             if self.g_automatic_demo_playback_countdown == 0 {
-                self.handle_demo_option_click();
+                self.play_random_demo(false);
             }
 
             if self.states.g_should_leave_main_menu != false {
@@ -1250,37 +1436,37 @@ impl Game<'_> {
             {
                 self.handle_ok_button_click();
             } else if self.keyboard.borrow_mut().g_is_f1_key_pressed {
-                self.play_demo(0);
+                self.play_demo(0, true);
             } else if self.keyboard.borrow_mut().g_is_f2_key_pressed {
-                self.play_demo(1);
+                self.play_demo(1, true);
             } else if self.keyboard.borrow_mut().g_is_f3_key_pressed {
-                self.play_demo(2);
+                self.play_demo(2, true);
             } else if self.keyboard.borrow_mut().g_is_f4_key_pressed {
-                self.play_demo(3);
+                self.play_demo(3, true);
             } else if self.keyboard.borrow_mut().g_is_f5_key_pressed {
-                self.play_demo(4);
+                self.play_demo(4, true);
             } else if self.keyboard.borrow_mut().g_is_f6_key_pressed {
-                self.play_demo(5);
+                self.play_demo(5, true);
             } else if self.keyboard.borrow_mut().g_is_f7_key_pressed {
-                self.play_demo(6);
+                self.play_demo(6, true);
             } else if self.keyboard.borrow_mut().g_is_f8_key_pressed {
-                self.play_demo(7);
+                self.play_demo(7, true);
             } else if self.keyboard.borrow_mut().g_is_f9_key_pressed {
-                self.play_demo(8);
+                self.play_demo(8, true);
             } else if self.keyboard.borrow_mut().g_is_f10_key_pressed {
-                self.play_demo(9);
+                self.play_demo(9, true);
             } else if self.keyboard.borrow_mut().g_is_numpad_divide_pressed
                 && self.demo_manager.demo_file_name.len() != 0
                 && self.demo_manager.file_is_demo
             {
                 self.demo_manager.g_is_sp_demo_available_to_run = 1;
-                self.play_demo(0);
+                self.play_demo(0, true);
             } else if self.keyboard.borrow_mut().g_is_f12_key_pressed
                 && self.demo_manager.demo_file_name.len() != 0
             {
                 self.demo_manager.g_is_sp_demo_available_to_run = 1;
                 self.states.g_should_leave_main_menu = true;
-                self.g_is_playing_demo = false;
+                self.states.g_is_playing_demo = false;
                 self.states.g_should_update_total_level_time = false;
                 self.states.g_has_user_cheated = true;
                 //prepareDemoRecordingFilename();
@@ -1344,8 +1530,8 @@ impl Game<'_> {
         }
 
         self.g_is_main_menu = false;
-        // savePlayerListData(); TODO : save !
-        // saveHallOfFameData();
+        self.save_player_list_data();
+        self.save_hall_of_fame_data();
     }
 
     fn draw_menu_title_and_demo_level_result(&mut self) {
@@ -1433,7 +1619,7 @@ impl Game<'_> {
     fn prepare_ranking_text_entries(&mut self) {
         struct RankingEntry {
             player_index: u8,
-            next_level_to_play: u8,
+            next_level_to_play: u16,
             hours: u8,
             minutes: u8,
             seconds: u8,
@@ -1486,10 +1672,11 @@ impl Game<'_> {
         self.g_ranking_text_entries[K_NUMBER_OF_PLAYERS + 2] = String::from(" ").repeat(22);
         self.g_ranking_text_entries[K_NUMBER_OF_PLAYERS + 3] = String::from(" ").repeat(22);
 
+        let last_level_index = self.level_manager.last_level_index() as u16;
         for i in 0..K_NUMBER_OF_PLAYERS {
             self.g_ranking_text_entries[i + 2] = format!(
                 "{} {:indent$} {:03}:{:02}:{:02}",
-                if ranking_entries[i].next_level_to_play == 0x71 {
+                if ranking_entries[i].next_level_to_play == last_level_index {
                     999
                 } else {
                     ranking_entries[i].next_level_to_play as u32
@@ -1504,8 +1691,8 @@ impl Game<'_> {
     }
 
     fn sub_4C407(&mut self) {
-        if self.g_level_failed {
-            self.g_level_failed = false;
+        if self.states.g_level_failed {
+            self.states.g_level_failed = false;
             self.draw_failed_level_result_screen();
             self.graphics.draw_menu_background();
 
@@ -2057,7 +2244,8 @@ impl Game<'_> {
             && mouse_x <= ok_button_descriptor.end_x
             && mouse_y <= ok_button_descriptor.end_y
         {
-            self.g_player_list_data[self.states.g_current_player_index] = Box::new(PlayerEntry::new());
+            self.g_player_list_data[self.states.g_current_player_index] =
+                Box::new(PlayerEntry::new(self.level_manager.g_number_of_levels));
         }
 
         self.draw_text_with_chars6_font_with_opaque_background_if_possible(
@@ -2093,7 +2281,7 @@ impl Game<'_> {
 
         let mut number_of_skipped_levels = 0;
 
-        for i in 0..K_NUMBER_OF_LEVELS {
+        for i in 0..self.level_manager.g_number_of_levels {
             if current_player_entry.level_state[i] == PlayerLevelState::Skipped {
                 number_of_skipped_levels += 1;
             }
@@ -2237,7 +2425,7 @@ impl Game<'_> {
             current_player_text,
         );
 
-        if current_player_entry.next_level_to_play == K_LAST_LEVEL_INDEX as u8 {
+        if current_player_entry.next_level_to_play == self.level_manager.last_level_index() as u16 {
             special_text = 1;
         }
 
@@ -2336,13 +2524,14 @@ impl Game<'_> {
     }
     fn handle_demo_option_click(&mut self) {
         log::info!("handle_demo_option_click");
+        self.play_random_demo(true);
+    }
+
+    fn play_random_demo(&mut self, should_show_signature: bool) {
         if self.demo_manager.read_demo_files() == 0 {
             return;
         }
 
-        self.states.g_should_leave_main_menu = true;
-        self.states.g_is_playing_demo = true;
-
         let mut number_of_demos = 0;
 
         let mut idx = 0; // usefull ? number_of_demos seems enough
@@ -2356,40 +2545,9 @@ impl Game<'_> {
 
         // This picks a random demo
         self.generate_random_seed_from_clock();
-        let demo_index = self.generate_random_number() % number_of_demos;
-        let mut demo_first_index =
-            self.demo_manager.g_demos.demo_first_indices[demo_index as usize];
-
-        // This only happens if there are no demos...
-        if demo_first_index == 0xffff {
-            self.states.g_should_leave_main_menu = false;
-            self.states.g_is_playing_demo = false;
-        }
-
-        let demo_level_number = self.demo_manager.g_demos.demo_data[demo_first_index as usize];
-        let mut final_level_number = demo_index;
-
+        let demo_index = self.states.generate_random_number() % number_of_demos;
         self.demo_manager.g_selected_original_demo_index = demo_index;
-        self.demo_manager.g_selected_original_demo_level_number = 0;
-
-        // This checks if the level number has its MSB to 0 and is a valid level number (1-111) for the original DEMO format
-        if demo_level_number <= 0x6F // 111
-            && demo_level_number != 0
-        {
-            self.demo_manager.g_selected_original_demo_level_number =
-                (self.demo_manager.g_selected_original_demo_level_number & 0xFF00)
-                    | (demo_level_number as usize); // mov byte ptr gSelectedOriginalDemoLevelNumber, al
-            final_level_number = demo_level_number as u16;
-        }
-
-        self.g_random_generator_seed = self.demo_manager.g_demo_random_seeds[demo_index as usize];
-        self.demo_manager.g_selected_original_demo_level_number = final_level_number as usize;
-
-        demo_first_index += 1; // To skip the level number
-        self.demo_manager.g_demo_current_input_index = demo_first_index;
-        self.demo_manager.word_5A33C = demo_first_index;
-        self.demo_manager.g_demo_current_input = UserInput::None;
-        self.demo_manager.g_demo_current_input_repeater_count = 1;
+        self.play_demo(demo_index, should_show_signature);
     }
 
     fn handle_controls_option_click(&mut self) {
@@ -2605,18 +2763,20 @@ impl Game<'_> {
             return;
         }
 
-        if self.states.g_current_selected_level_index == K_LAST_LEVEL_INDEX as u8 {
-            log::debug!("self.states.g_current_selected_level_index == K_LAST_LEVEL_INDEX");
+        let number_of_levels = self.level_manager.g_number_of_levels;
+        let last_level_index = self.level_manager.last_level_index() as u16;
+        if self.states.g_current_selected_level_index == last_level_index {
+            log::debug!("self.states.g_current_selected_level_index == last_level_index");
             let mut number_of_completed_levels = 0;
 
-            for i in 0..K_NUMBER_OF_LEVELS {
+            for i in 0..number_of_levels {
                 if self.g_player_list_data[self.states.g_current_player_index].level_state[i]
                     == PlayerLevelState::Completed
                 {
                     number_of_completed_levels += 1;
                 }
             }
-            if number_of_completed_levels == K_NUMBER_OF_LEVELS {
+            if number_of_completed_levels == number_of_levels {
                 self.show_congratulations_screen();
                 return;
             } else {
@@ -2629,7 +2789,7 @@ impl Game<'_> {
                 );
                 return;
             }
-        } else if self.states.g_current_selected_level_index > K_NUMBER_OF_LEVELS as u8 {
+        } else if self.states.g_current_selected_level_index > number_of_levels as u16 {
             return;
         }
 
@@ -2649,7 +2809,7 @@ impl Game<'_> {
             return;
         }
         self.states.g_should_leave_main_menu = true;
-        self.g_is_playing_demo = false;
+        self.states.g_is_playing_demo = false;
         if current_level_color == K_COMPLETED_LEVEL_ENTRY_COLOR {
             self.states.g_should_update_total_level_time = false;
         } else {
@@ -2778,7 +2938,8 @@ impl Game<'_> {
             self.g_level_list_throttle_current_counter -= 1;
         }
 
-        if self.states.g_current_selected_level_index >= 113 {
+        let last_level_index = self.level_manager.last_level_index() as u16;
+        if self.states.g_current_selected_level_index >= last_level_index {
             return;
         }
         self.states.g_current_selected_level_index += 1;
@@ -2972,14 +3133,21 @@ impl Game<'_> {
             return;
         }
 
+        // Players are saved in the original format for level sets of 111 levels
+        let players: Vec<PlayerEntry> = self
+            .g_player_list_data
+            .iter()
+            .map(|player| (**player).clone())
+            .collect();
         let path = format!("{}/{}", RESSOURCES_PATH, G_PLAYERS_LST_FILENAME);
-        let player_lst_file_path = Path::new(&path);
-        let mut file = File::create(player_lst_file_path)
-            .expect(format!("Error while opening {}", G_PLAYERS_LST_FILENAME).as_str());
-
-        for i in 0..K_NUMBER_OF_PLAYERS {
-            file.write_all(&self.g_player_list_data[i].to_raw())
-                .expect(format!("Error while writing to {}", G_PLAYERS_LST_FILENAME).as_str());
+        // The game goes on when the progress can't be saved
+        let result =
+            player_list::write_player_list(&players, self.level_manager.g_number_of_levels)
+                .and_then(|player_list_data| {
+                    std::fs::write(&path, player_list_data).map_err(|error| error.to_string())
+                });
+        if let Err(error) = result {
+            error!("{}: {}", G_PLAYERS_LST_FILENAME, error);
         }
     }
 
@@ -3001,28 +3169,35 @@ impl Game<'_> {
     }
 
     fn change_player_current_level_state(&mut self) {
-        if self.g_is_playing_demo {
+        if self.states.g_is_playing_demo {
             return;
         }
-        if self.g_has_user_cheated {
+        if self.states.g_has_user_cheated {
             return;
         }
         let previous_state = self.states.g_current_player_level_state;
         self.states.g_current_player_level_state = PlayerLevelState::NotCompleted;
 
         self.g_player_list_data[self.states.g_current_player_index].level_state
-            [self.states.g_current_selected_level_index as usize] = previous_state;
+            [self.states.g_current_selected_level_index as usize - 1] = previous_state;
 
-        self.states.g_current_selected_level_index += 1;
+        // A failed level is played again
+        if previous_state != PlayerLevelState::NotCompleted {
+            self.states.g_current_selected_level_index += 1;
+        }
         self.update_hall_of_fame_entries(); // 01ED:6618
 
+        // Updates the next level to play of the player before saving it
+        self.g_should_autoselect_next_level_to_play = false;
+        self.prepare_level_data_for_current_player();
+
         // Added by me to prevent losing progress when switching levelsets after finishing a level
         self.save_player_list_data();
         self.save_hall_of_fame_data();
     }
 
     fn update_hall_of_fame_entries(&mut self) {
-        if self.g_is_playing_demo {
+        if self.states.g_is_playing_demo {
             return;
         }
 
@@ -3034,13 +3209,13 @@ impl Game<'_> {
 
         let mut number_of_completed_levels = 0;
 
-        for i in 0..K_NUMBER_OF_LEVELS {
+        for i in 0..self.level_manager.g_number_of_levels {
             if current_player_entry.level_state[i] == PlayerLevelState::Completed {
                 number_of_completed_levels += 1;
             }
         }
 
-        if number_of_completed_levels != K_NUMBER_OF_LEVELS {
+        if number_of_completed_levels != self.level_manager.g_number_of_levels {
             return;
         }
 
@@ -3417,8 +3592,6 @@ impl Game<'_> {
                 self.level_manager.g_levels_dat_filename.get(0..8).unwrap(),
                 new_suffix
             );
-            self.demo_manager.g_levels_dat_filename =
-                self.level_manager.g_levels_dat_filename.clone();
 
             let path = format!(
                 "{}/{}",
@@ -3440,9 +3613,10 @@ impl Game<'_> {
         self.demo_manager.read_demo_files();
 
         if self.g_is_forced_cheat_mode {
-            self.g_player_list_data[0].level_state = [PlayerLevelState::Skipped; K_NUMBER_OF_LEVELS]
+            self.g_player_list_data[0].level_state = vec![PlayerLevelState::Skipped; self.level_manager.g_number_of_levels]
         } else {
-            self.g_player_list_data = Box::new([(); K_NUMBER_OF_PLAYERS].map(|_| Box::new(PlayerEntry::new())));
+            let number_of_levels = self.level_manager.g_number_of_levels;
+            self.g_player_list_data = Box::new([(); K_NUMBER_OF_PLAYERS].map(|_| Box::new(PlayerEntry::new(number_of_levels))));
             self.g_hall_of_fame_data =
             Box::new([(); K_NUMBER_OF_HALL_OF_FAME_ENTRIES].map(|_| Box::new(HallOfFameEntry::new())));
             self.read_hall_fame_lst();
@@ -3487,7 +3661,10 @@ impl Game<'_> {
             24,
             70,
             15,
-            "YOU HAVE COMPLETED ALL 111 LEVELS OF SUPAPLEX".to_string(),
+            format!(
+                "YOU HAVE COMPLETED ALL {} LEVELS OF SUPAPLEX",
+                self.level_manager.g_number_of_levels
+            ),
         );
         self.draw_text_with_chars6_font_with_opaque_background_if_possible(
             64,
@@ -3533,36 +3710,7 @@ impl Game<'_> {
 
         self.demo_manager
             .g_sp_demo_filename
-            .replace_range(9..11, current_suffix);
-    }
-
-    fn initialize_game_info(&mut self) {
-        self.states.g_is_murphy_looking_left = false;
-        self.states.g_should_kill_murphy = false;
-        self.states.g_should_exit_level = false;
-        self.states.g_quit_level_countdown = 0;
-        self.states.g_number_of_remaining_red_disks = 0;
-        self.states.g_additional_info_in_game_panel_frame_counter = 0;
-        self.states.g_murphy_yawn_and_sleep_counter = 0;
-        self.states.g_last_drawn_minutes_and_seconds = 0xffff;
-        self.states.g_last_drawn_hours = 0xff; // 255
-        self.states.g_is_game_running = true;
-        self.states.g_aux_game_seconds_20ms_accumulator = 0;
-        self.states.g_game_seconds = 0;
-        self.states.g_game_minutes = 0;
-        self.states.g_game_hours = 0;
-        self.states.g_is_explosion_started = false;
-        self.states.g_terminal_max_frames_to_next_scroll = 0x7F; // 127
-        self.states.g_are_yellow_disks_detonated = false;
-        self.states.g_frame_counter = 0;
-        //    mov byte ptr word_510C1, 1
-        //    mov byte ptr word_510C1+1, 0
-        self.states.g_should_show_game_panel = true;
-        self.states.g_current_panel_height = K_PANEL_BITMAP_HEIGHT;
-        self.states.g_are_enemies_frozen = false;
-        self.states.g_is_murphy_going_through_portal &= 0xff00; // mov byte ptr gIsMurphyGoingThroughPortal, 0
-        self.states.g_planted_red_disk_countdown = 0;
-        self.states.g_planted_red_disk_position = 0;
+            .replace_range(0..2, current_suffix);
     }
 
     fn draw_fixed_level(&mut self) {
@@ -3760,38 +3908,9 @@ impl Game<'_> {
 
         for tile_y in 1..(K_LEVEL_HEIGHT - 1) {
             for tile_x in 1..(K_LEVEL_WIDTH - 1) {
-                let bitmap_tile_x = tile_x - 1;
-                let bitmap_tile_y = tile_y - 1;
-
-                let start_dst_x = K_LEVEL_EDGE_SIZE as usize + bitmap_tile_x * K_TILE_SIZE as usize;
-                let start_dst_y = K_LEVEL_EDGE_SIZE as usize + bitmap_tile_y * K_TILE_SIZE as usize;
-                let mut tile_value =
-                    self.states.g_current_level_state[tile_y * K_LEVEL_WIDTH + tile_x].tile;
-
-                // Tile values greater than the official ones (including 40, the invisible wall) will be rendered as empty
-                // spaces, to prevent issues even with custom graphics.
-                //
-                if tile_value >= LevelTileType::Count as u8 {
-                    tile_value = LevelTileType::Space as u8;
-                }
-
-                let start_src_x = tile_value as usize * K_TILE_SIZE;
-
-                for y in 0..K_TILE_SIZE {
-                    for x in 0..K_TILE_SIZE {
-                        let dst_address =
-                            (start_dst_y + y) * (K_LEVEL_BITMAP_WIDTH) + (start_dst_x) + x;
-                        let src_address = (y * (K_FIXED_BITMAP_WIDTH)) + start_src_x + x;
-                        let color = self
-                            .graphics
-                            .get_pixel_from_bitmap(BitmapType::FixedDecoded, src_address as usize);
-                        self.graphics.set_pixel(
-                            DestinationSurface::Level,
-                            dst_address as usize,
-                            color,
-                        );
-                    }
-                }
+                let position = tile_y * K_LEVEL_WIDTH + tile_x;
+                self.graphics
+                    .draw_fixed_tile(self.states.g_current_level_state[position].tile, position);
             }
         }
     }
@@ -3808,39 +3927,15 @@ impl Game<'_> {
             return;
         }
 
-        if false
-        //gIsRecordingDemo != 0
+        if self.demo_manager.g_is_recording_demo
         // Recording demo?
-        { /*
-             self.graphics.draw_text_with_chars8_font_to_game_panel(
-                 72,
-                 3,
-                 8,
-                 "  DEMO  ".to_string(),
-             );
-             self.graphics.draw_text_with_chars8_font_to_game_panel(
-                 16,
-                 14,
-                 8,
-                 gCurrentDemoLevelName,
-             );
-             self.graphics.draw_text_with_chars8_font_to_game_panel(
-                 64,
-                 14,
-                 8,
-                 gRecordingDemoMessage,
-             );*/
-        } else if self.g_is_playing_demo
-        // Playing demo?
         {
-            let demo_level_name = self.level_manager.g_level_list_data
-                [self.demo_manager.g_selected_original_demo_level_number]
-                .name
-                .clone();
-            let demo_level_number = format!(
-                "{:03}",
-                self.demo_manager.g_selected_original_demo_level_number
-            );
+            let demo_level_number = self
+                .states
+                .g_current_level_name
+                .get(0..3)
+                .unwrap_or("")
+                .to_string();
 
             self.graphics.draw_text_with_chars8_font_to_game_panel(
                 72,
@@ -3848,6 +3943,50 @@ impl Game<'_> {
                 8,
                 "  DEMO  ".to_string(),
             );
+            self.graphics
+                .draw_text_with_chars8_font_to_game_panel(16, 14, 8, demo_level_number);
+            self.graphics.draw_text_with_chars8_font_to_game_panel(
+                64,
+                14,
+                8,
+                self.demo_manager.recording_demo_message.clone(),
+            );
+        } else if self.states.g_is_playing_demo
+        // Playing demo?
+        {
+            // Levels without a number, like the ones of .SP files, can have short or empty names
+            let demo_level_name = self
+                .states
+                .g_current_level_name
+                .get(4..)
+                .unwrap_or("")
+                .to_string();
+            let demo_level_number = self
+                .states
+                .g_current_level_name
+                .get(0..3)
+                .unwrap_or("")
+                .to_string();
+
+            // Demos failing the checksum validation were edited or written by Megaplex
+            let is_demo_tampered = !self.demo_manager.is_original_demo()
+                && self
+                    .demo_manager
+                    .g_demos
+                    .validate_checksum(self.demo_manager.g_demo_index_or_demo_level_number as usize)
+                    .is_some_and(|validation| !validation.is_valid());
+            let demo_label = if is_demo_tampered {
+                "BAD DEMO"
+            } else {
+                "  DEMO  "
+            };
+
+            self.graphics.draw_text_with_chars8_font_to_game_panel(
+                72,
+                3,
+                8,
+                demo_label.to_string(),
+            );
             self.graphics
                 .draw_text_with_chars8_font_to_game_panel(16, 14, 8, demo_level_number);
             self.graphics
@@ -3866,18 +4005,24 @@ impl Game<'_> {
                 self.states
                     .g_current_level_name
                     .get(0..3)
-                    .unwrap()
+                    .unwrap_or("")
                     .to_string(),
             );
+            // Demos which couldn't be saved are told instead of the level name
+            let level_name_or_message = if self.demo_manager.g_has_demo_recording_failed {
+                K_DEMO_NOT_SAVED_MESSAGE.to_string()
+            } else {
+                self.states
+                    .g_current_level_name
+                    .get(4..)
+                    .unwrap_or("")
+                    .to_string()
+            };
             self.graphics.draw_text_with_chars8_font_to_game_panel(
                 64,
                 14,
                 8,
-                self.states
-                    .g_current_level_name
-                    .get(4..)
-                    .unwrap()
-                    .to_string(),
+                level_name_or_message,
             );
         }
 
@@ -3908,6 +4053,36 @@ impl Game<'_> {
         );
     }
 
+    fn draw_number_of_remaining_red_disks(&mut self) {
+        if self.states.g_additional_info_in_game_panel_frame_counter == 0 {
+            return;
+        }
+
+        self.states.g_additional_info_in_game_panel_frame_counter -= 1;
+        if self.graphics.fast_mode == FastModeType::Ultra {
+            return;
+        }
+
+        if self.states.g_additional_info_in_game_panel_frame_counter == 0 {
+            self.graphics
+                .draw_text_with_chars8_font_to_game_panel(152, 3, 8, "   ".to_string());
+            return;
+        }
+
+        let color = if self.states.g_number_of_remaining_red_disks == 0 {
+            8
+        } else {
+            6
+        };
+
+        self.graphics.draw_text_with_chars8_font_to_game_panel(
+            152,
+            3,
+            color,
+            format!("{:03}", self.states.g_number_of_remaining_red_disks),
+        );
+    }
+
     fn draw_game_time(&mut self) {
         if self.graphics.fast_mode == FastModeType::Ultra {
             return;
@@ -3954,193 +4129,16 @@ impl Game<'_> {
         }
     }
 
-    fn convert_to_easy_tiles(&mut self) -> u16 {
-        let mut number_of_infotrons = 0;
-        let mut number_of_something = 0; // this is bx, just counts the number of tiles so technically is same as cx at this point… probably a return value but I don't see it used anywhere???
-
-        for i in 0..K_LEVEL_SIZE {
-            let current_tile = i;
-            number_of_something += 1;
-
-            if self.states.g_current_level_state[current_tile].tile == 0xf1 {
-                self.states.g_current_level_state[current_tile].tile = LevelTileType::Explosion as u8;
-                continue; // jmp short loc_4A3B0
-            }
-
-            if self.g_is_game_busy != true {
-                if self.states.g_current_level_state[current_tile].tile == LevelTileType::Infotron as u8 {
-                    number_of_infotrons += 1;
-                    continue; // jmp short loc_4A3B0
-                }
-            }
-            // TODO: what are these gIsGameBusy for??
-            if self.g_is_game_busy == true
-                || self.states.g_current_level_state[current_tile].state != 0
-                || self.states.g_current_level_state[current_tile].tile != LevelTileType::SnikSnak as u8
-            //jz  short loc_4A34B
-            {
-                if self.g_is_game_busy == true
-                    || self.states.g_current_level_state[current_tile].state != 0
-                    || self.states.g_current_level_state[current_tile].tile != LevelTileType::Electron as u8
-                //jz  short loc_4A379
-                {
-                    if (self.states.g_current_level_state[current_tile].state == 0
-                        && self.states.g_current_level_state[current_tile].tile == LevelTileType::HorizontalChipLeft as u8)
-                        || (self.states.g_current_level_state[current_tile].state == 0
-                            && self.states.g_current_level_state[current_tile].tile == LevelTileType::HorizontalChipRight as u8)
-                        || (self.states.g_current_level_state[current_tile].state == 0
-                            && self.states.g_current_level_state[current_tile].tile == LevelTileType::HorizontalChipTop as u8)
-                        || (self.states.g_current_level_state[current_tile].state == 0
-                            && self.states.g_current_level_state[current_tile].tile == LevelTileType::HorizontalChipBottom as u8)
-                    {
-                        self.states.g_current_level_state[current_tile].tile = LevelTileType::Chip as u8; // mov word ptr [si], 5
-                        self.states.g_current_level_state[current_tile].state = 0;
-                        continue; // jmp short loc_4A3B0
-                    }
-                    if self.states.g_current_level_state[current_tile].state == 0
-                        && self.states.g_current_level_state[current_tile].tile >= LevelTileType::Hardware2 as u8
-                        && self.states.g_current_level_state[current_tile].tile <= LevelTileType::Hardware11 as u8
-                    {
-                        self.states.g_current_level_state[current_tile].tile = LevelTileType::Hardware as u8; // mov word ptr [si], 6
-                        self.states.g_current_level_state[current_tile].state = 0;
-                        continue; // jmp short loc_4A3B0
-                    }
-
-                    if self.states.g_current_level_state[current_tile].state == 0
-                        && self.states.g_current_level_state[current_tile].tile >= LevelTileType::SportRight as u8
-                        && self.states.g_current_level_state[current_tile].tile <= LevelTileType::SportUp as u8
-                    {
-                        self.states.g_current_level_state[current_tile].tile -= 4; // Converts Sport[Direction] to Port[Direction]
-                        self.states.g_current_level_state[current_tile].state = 1;
-                        continue;
-                    }
-
-                    continue;
-                }
-            }
-
-            let left_tile = i-1;
-            let above_tile = i-K_LEVEL_WIDTH;
-            let right_tile = i + 1;
-
-            if self.states.g_current_level_state[current_tile].state != 0 || self.states.g_current_level_state[current_tile].tile != LevelTileType::Electron as u8
-            //jz  short loc_4A379
-            {
-                if self.states.g_current_level_state[left_tile].tile == (LevelTileType::Space as u8) && self.states.g_current_level_state[left_tile].state == 0
-                //cmp word ptr [si-2], 0
-                {
-                    self.states.g_current_level_state[current_tile].state = 1;
-                    continue; // jmp short loc_4A3B0
-                }
-                if self.states.g_current_level_state[above_tile].tile == (LevelTileType::Space as u8) && self.states.g_current_level_state[above_tile].state == 0
-                //cmp word ptr [si-78h], 0
-                {
-                    self.states.g_current_level_state[above_tile].state = 0x10;
-                    self.states.g_current_level_state[above_tile].tile = LevelTileType::SnikSnak as u8;
-                    // mov word ptr [si], 0FFFFh
-                    self.states.g_current_level_state[current_tile].state = 0xFF;
-                    self.states.g_current_level_state[current_tile].tile = 0xFF;
-                    continue; // jmp short loc_4A3B0
-                }
-                if self.states.g_current_level_state[right_tile].tile == (LevelTileType::Space as u8) && self.states.g_current_level_state[right_tile].state == 0
-                //cmp word ptr [si+2], 0
-                {
-                    self.states.g_current_level_state[right_tile].state = 0x28;
-                    self.states.g_current_level_state[right_tile].tile = LevelTileType::SnikSnak as u8;
-                    self.states.g_current_level_state[current_tile].state = 0xFF;
-                    self.states.g_current_level_state[current_tile].tile = 0xFF;
-                    continue; // jmp short loc_4A3B0
-                }
-
-                continue;
-            }
-            if self.states.g_current_level_state[left_tile].tile == LevelTileType::Space as u8 && self.states.g_current_level_state[left_tile].state == 0
-            //cmp word ptr [si-2], 0
-            {
-                self.states.g_current_level_state[current_tile].state = 1; //mov byte ptr [si+1], 1
-                continue; // jmp short loc_4A3B0
-            }
-            if self.states.g_current_level_state[above_tile].tile == LevelTileType::Space as u8 && self.states.g_current_level_state[above_tile].state == 0
-            //cmp word ptr [si-78h], 0
-            {
-                // mov word ptr [si-78h], 1018h
-                self.states.g_current_level_state[above_tile].state = 0x10;
-                self.states.g_current_level_state[above_tile].tile = LevelTileType::Electron as u8;
-                // mov word ptr [si], 0FFFFh
-                self.states.g_current_level_state[current_tile].state = 0xFF;
-                self.states.g_current_level_state[current_tile].tile = 0xFF;
-                continue; // jmp short loc_4A3B0
-            }
-            if self.states.g_current_level_state[right_tile].tile == LevelTileType::Space as u8 && self.states.g_current_level_state[right_tile].state == 0
-            //cmp word ptr [si+2], 0
-            {
-                self.states.g_current_level_state[right_tile].state = 0x28;
-                self.states.g_current_level_state[right_tile].tile = LevelTileType::Electron as u8;
-                self.states.g_current_level_state[current_tile].state = 0xFF;
-                self.states.g_current_level_state[current_tile].tile = 0xFF;
-                continue; // jmp short loc_4A3B0
-            }
-        }
-
-        number_of_infotrons
-    }
-
-    fn find_murphy(&mut self) {
-        for i in 0..K_LEVEL_SIZE {
-            if self.states.g_current_level.tiles[i] == LevelTileType::Murphy as u8 {
-                self.states.g_murphy_location = i;
-                break;
-            }
-        }
-
-        self.scroll_to_murphy();
-    }
-
     fn scroll_to_murphy(&mut self) {
-        // Parameters:
-        // - si: murphy location * 2
-        // - al: murphy location
-
-        self.states.g_murphy_tile_x = self.states.g_murphy_location % K_LEVEL_WIDTH; // stores X coord
-        self.states.g_murphy_tile_y = self.states.g_murphy_location / K_LEVEL_WIDTH; // stores Y coord
-
-        self.states.g_murphy_position_x = self.states.g_murphy_tile_x * K_TILE_SIZE;
-        self.states.g_murphy_position_y = self.states.g_murphy_tile_y * K_TILE_SIZE;
-
         self.graphics
             .draw_moving_frame(304, 132, self.states.g_murphy_location);
-        self.update_scroll_offset();
+        self.update_scroll_offset(0);
 
         self.graphics.video_loop();
     }
 
-    fn reset_number_of_infotrons(&mut self, number_of_infotrons_found_in_level: u16) {
-        // In the original game, the number of infotrons found in a level is stored in a 2-bytes variable,
-        // however, when stored for its use in the game, it's stored in a 1-byte variable.
-        //
-        let mut number_of_infotrons = (number_of_infotrons_found_in_level & 0xFF) as u8;
-        if self.states.g_number_of_info_trons != 0 {
-            number_of_infotrons = self.states.g_number_of_info_trons;
-        }
-
-        self.states.g_number_of_remaining_infotrons = number_of_infotrons;
-
-        self.states.g_total_number_of_infotrons = number_of_infotrons;
-        self.draw_number_of_remaining_infotrons();
-    }
-    fn update_scroll_offset(&mut self) {
-        let mut random_number = 0;
-
-        // This random number is used to generate the shaking effect on explosions.
-        // The original game generates this random number here for _every_ explosion, even if
-        // normally only Murphy's explosion will make the screen shake. However it's necessary
-        // to do this here to make sure the right sequence of random numbers is generated when
-        // there are explosions in the level.
-        //
-        if self.states.g_is_explosion_started {
-            random_number = self.generate_random_number();
-        }
-
+    /// Scrolls the level to Murphy. The random number makes the screen shake on explosions.
+    fn update_scroll_offset(&mut self, mut random_number: u16) {
         let mut scroll_x = self.states.g_murphy_position_x as i32;
         let mut scroll_y = self.states.g_murphy_position_y as i32;
         scroll_x -= K_SCREEN_WIDTH as i32 / 2; // 152
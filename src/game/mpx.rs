@@ -0,0 +1,258 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Level packs of the Megaplex editor (MPX files). They start with a header:
+//   "MPX ", the version (1) and the number of levels, as 16 bits little endian values
+// followed by an entry per level:
+//   width and height as 16 bits values, offset and size of the level as 32 bits values
+// Offsets start at 1, like file positions in Visual Basic which Megaplex was written with. Each
+// level is stored like in .SP files: its width x height tiles, the 96 bytes of level info, then the
+// demo recorded on the level, if any. Special port positions count tiles in the width of the level.
+
+use crate::game::globals::*;
+use crate::game::level::Level;
+
+const K_MPX_MAGIC: &[u8; 4] = b"MPX ";
+const K_MPX_VERSION: u16 = 1;
+const K_MPX_HEADER_LENGTH: usize = 8;
+const K_MPX_LEVEL_ENTRY_LENGTH: usize = 12;
+const K_LEVEL_INFO_LENGTH: usize = K_LEVEL_DATA_LENGTH - K_LEVEL_SIZE;
+
+pub struct MpxLevel {
+    pub level: Level,
+    pub demo_data: Vec<u8>, // Empty when the level has no demo
+}
+
+pub fn is_mpx(data: &[u8]) -> bool {
+    data.starts_with(K_MPX_MAGIC)
+}
+
+/// Reads the levels of a MPX file. Levels smaller than the original ones are surrounded by hardware
+/// to be played, larger ones can't be.
+pub fn read_mpx(data: &[u8]) -> Result<Vec<MpxLevel>, String> {
+    if !is_mpx(data) || data.len() < K_MPX_HEADER_LENGTH {
+        return Err(String::from("This is not a MPX file"));
+    }
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]) as usize;
+    let read_u32 =
+        |offset: usize| u32::from_le_bytes(data[offset..(offset + 4)].try_into().unwrap()) as usize;
+
+    let version = read_u16(4);
+    if version != K_MPX_VERSION as usize {
+        return Err(format!("The MPX version {} is not supported", version));
+    }
+    let number_of_levels = read_u16(6);
+    if data.len() < K_MPX_HEADER_LENGTH + number_of_levels * K_MPX_LEVEL_ENTRY_LENGTH {
+        return Err(String::from("The MPX file is truncated"));
+    }
+
+    let mut levels = Vec::with_capacity(number_of_levels);
+    for i in 0..number_of_levels {
+        let entry_offset = K_MPX_HEADER_LENGTH + i * K_MPX_LEVEL_ENTRY_LENGTH;
+        let width = read_u16(entry_offset);
+        let height = read_u16(entry_offset + 2);
+        let level_offset = read_u32(entry_offset + 4);
+        let level_size = read_u32(entry_offset + 8);
+
+        if width == 0 || height == 0 || width > K_LEVEL_WIDTH || height > K_LEVEL_HEIGHT {
+            return Err(format!(
+                "Level {} is {}x{}, only levels up to {}x{} are supported",
+                i + 1,
+                width,
+                height,
+                K_LEVEL_WIDTH,
+                K_LEVEL_HEIGHT
+            ));
+        }
+        let tiles_length = width * height;
+        let level_record = level_offset
+            .checked_sub(1)
+            .and_then(|start| data.get(start..(start + level_size)))
+            .filter(|level_record| level_record.len() >= tiles_length + K_LEVEL_INFO_LENGTH)
+            .ok_or_else(|| format!("Level {} is outside of the MPX file", i + 1))?;
+
+        let (tiles, level_record) = level_record.split_at(tiles_length);
+        let (level_info, demo_data) = level_record.split_at(K_LEVEL_INFO_LENGTH);
+        let mut level_data = [LevelTileType::Hardware as u8; K_LEVEL_DATA_LENGTH];
+        for (row, row_tiles) in tiles.chunks_exact(width).enumerate() {
+            let row_start = row * K_LEVEL_WIDTH;
+            level_data[row_start..(row_start + width)].copy_from_slice(row_tiles);
+        }
+        level_data[K_LEVEL_SIZE..].copy_from_slice(level_info);
+
+        let mut level = Level::from_raw(i, &level_data)
+            .map_err(|error| format!("Level {}: {}", i + 1, error))?;
+        for special_port_info in
+            level.special_ports_info[..level.number_of_special_ports as usize].iter_mut()
+        {
+            // Positions count 2 bytes per tile
+            let position = special_port_info.position as usize / 2;
+            let (x, y) = (position % width, position / width);
+            if y >= height {
+                return Err(format!(
+                    "Level {}: a special port is outside of the {}x{} level",
+                    i + 1,
+                    width,
+                    height
+                ));
+            }
+            special_port_info.position = ((y * K_LEVEL_WIDTH + x) * 2) as u16;
+        }
+        levels.push(MpxLevel {
+            level,
+            demo_data: demo_data.to_vec(),
+        });
+    }
+    Ok(levels)
+}
+
+/// Writes the given levels, and their demos, into a MPX file
+pub fn write_mpx(levels: &[MpxLevel]) -> Result<Vec<u8>, String> {
+    let number_of_levels = u16::try_from(levels.len())
+        .map_err(|_| format!("A MPX file can't hold {} levels", levels.len()))?;
+
+    let mut data = Vec::new();
+    data.extend_from_slice(K_MPX_MAGIC);
+    data.extend_from_slice(&K_MPX_VERSION.to_le_bytes());
+    data.extend_from_slice(&number_of_levels.to_le_bytes());
+
+    let mut level_offset = K_MPX_HEADER_LENGTH + levels.len() * K_MPX_LEVEL_ENTRY_LENGTH;
+    for mpx_level in levels {
+        let level_size = K_LEVEL_DATA_LENGTH + mpx_level.demo_data.len();
+        data.extend_from_slice(&(K_LEVEL_WIDTH as u16).to_le_bytes());
+        data.extend_from_slice(&(K_LEVEL_HEIGHT as u16).to_le_bytes());
+        data.extend_from_slice(&(level_offset as u32 + 1).to_le_bytes());
+        data.extend_from_slice(&(level_size as u32).to_le_bytes());
+        level_offset += level_size;
+    }
+
+    for mpx_level in levels {
+        data.extend_from_slice(&mpx_level.level.to_raw());
+        data.extend_from_slice(&mpx_level.demo_data);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn shipped_levels(number_of_levels: usize) -> Vec<Level> {
+        let levels_dat_path = format!(
+            "{}/{}/LEVELS.DAT",
+            env!("CARGO_MANIFEST_DIR"),
+            RESSOURCES_PATH
+        );
+        fs::read(levels_dat_path).unwrap()[..(number_of_levels * K_LEVEL_DATA_LENGTH)]
+            .chunks_exact(K_LEVEL_DATA_LENGTH)
+            .enumerate()
+            .map(|(i, level_data)| Level::from_raw(i, level_data).unwrap())
+            .collect()
+    }
+
+    /// Builds a MPX file with a single level of the given size
+    fn single_level_mpx(width: u16, height: u16, level_record: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(K_MPX_MAGIC);
+        data.extend_from_slice(&K_MPX_VERSION.to_le_bytes());
+        data.extend_from_slice(&1_u16.to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(
+            &(K_MPX_HEADER_LENGTH as u32 + K_MPX_LEVEL_ENTRY_LENGTH as u32 + 1).to_le_bytes(),
+        );
+        data.extend_from_slice(&(level_record.len() as u32).to_le_bytes());
+        data.extend_from_slice(level_record);
+        data
+    }
+
+    #[test]
+    fn levels_and_demos_are_written_and_read_back_identically() {
+        let demo_data = vec![0x81, 0x24, 0x13, 0xFF, b'M', b'e', 0xFF];
+        let mpx_levels: Vec<MpxLevel> = shipped_levels(3)
+            .into_iter()
+            .enumerate()
+            .map(|(i, level)| MpxLevel {
+                level,
+                demo_data: if i == 1 {
+                    demo_data.clone()
+                } else {
+                    Vec::new()
+                },
+            })
+            .collect();
+
+        let data = write_mpx(&mpx_levels).unwrap();
+        let read_levels = read_mpx(&data).unwrap();
+        assert_eq!(read_levels.len(), mpx_levels.len());
+        for (read_level, mpx_level) in read_levels.iter().zip(&mpx_levels) {
+            assert_eq!(read_level.level.to_raw(), mpx_level.level.to_raw());
+            assert_eq!(read_level.demo_data, mpx_level.demo_data);
+        }
+        assert_eq!(write_mpx(&read_levels).unwrap(), data);
+    }
+
+    #[test]
+    fn smaller_levels_are_surrounded_by_hardware() {
+        let (width, height) = (10, 5);
+        let reference_level = shipped_levels(1).remove(0).to_raw();
+        let mut level_record = vec![LevelTileType::Space as u8; width * height];
+        level_record[2 * width + 3] = LevelTileType::PortRight as u8;
+        let mut level_info = reference_level[K_LEVEL_SIZE..].to_vec();
+        level_info[0x1F] = 1; // Number of special ports
+        level_info[0x20..0x22].copy_from_slice(&(((2 * width + 3) * 2) as u16).to_be_bytes());
+        level_record.extend_from_slice(&level_info);
+        level_record.extend_from_slice(&[0x01, 0x24, 0xFF]);
+
+        let mpx_level = read_mpx(&single_level_mpx(
+            width as u16,
+            height as u16,
+            &level_record,
+        ))
+        .unwrap()
+        .remove(0);
+        let level = mpx_level.level;
+        assert_eq!(
+            level.tiles[2 * K_LEVEL_WIDTH + 3],
+            LevelTileType::PortRight as u8
+        );
+        assert_eq!(
+            level.tiles[2 * K_LEVEL_WIDTH + width],
+            LevelTileType::Hardware as u8
+        );
+        assert_eq!(
+            level.tiles[height * K_LEVEL_WIDTH],
+            LevelTileType::Hardware as u8
+        );
+        assert_eq!(
+            level.special_ports_info[0].position as usize,
+            (2 * K_LEVEL_WIDTH + 3) * 2
+        );
+        assert_eq!(
+            level.name,
+            Level::from_raw(0, &reference_level).unwrap().name
+        );
+        assert_eq!(mpx_level.demo_data, [0x01, 0x24, 0xFF]);
+    }
+
+    #[test]
+    fn levels_larger_than_the_original_ones_are_rejected() {
+        let level_record = vec![0; 61 * 24 + K_LEVEL_INFO_LENGTH];
+        assert!(read_mpx(&single_level_mpx(61, 24, &level_record)).is_err());
+    }
+}
@@ -0,0 +1,923 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Murphy's per-frame update (updateMurphy). Murphy's current action is stored in the state of his
+// tile, with the values of the original game. A state of 0 means Murphy is idle and can take a new
+// action. Each action lasts as long as its animation, and its end is handled on the last frame.
+//
+// Moves into an empty tile, a base or an infotron move Murphy to the next tile right away, the tile
+// he leaves is marked with K_MURPHY_TRAIL_WORD. For the other actions, Murphy stays in his tile
+// until the action is over.
+
+use crate::game::animation::{K_MURPHY_ANIMATION_DESCRIPTOR, K_MURPHY_ANIMATION_FRAME_COORDINATES};
+use crate::game::bugs::is_bug_active;
+use crate::game::explosions::K_INFOTRON_EXPLOSION_FLAG;
+use crate::game::game_states::GameStates;
+use crate::game::globals::*;
+use crate::game::graphics::K_TILE_SIZE;
+use crate::game::keyboard::UserInput;
+use crate::game::simulation::{
+    adjacent_position, Direction, K_ENEMY_LEAVING_TILE, K_FALLING_DESTINATION_WORD,
+    K_MURPHY_TRAIL_WORD, K_QUIT_LEVEL_COUNTDOWN, K_ROLLING_DESTINATION_WORD,
+};
+use crate::game::sounds::SoundEffect;
+
+// Murphy's states, by direction (up, left, down, right) when the action has one. Eating a base on
+// the left uses the same state as moving left in the original game.
+const K_MURPHY_MOVING_STATES: [u8; 4] = [0x01, 0x02, 0x03, 0x04];
+const K_MURPHY_EATING_BASE_STATES: [u8; 4] = [0x05, 0x02, 0x07, 0x08];
+const K_MURPHY_EATING_INFOTRON_STATES: [u8; 4] = [0x09, 0x0A, 0x0B, 0x0C];
+pub const K_MURPHY_STATE_EXITING: u8 = 0x0D;
+const K_MURPHY_STATE_PUSHING_ZONK_LEFT: u8 = 0x0E;
+const K_MURPHY_STATE_PUSHING_ZONK_RIGHT: u8 = 0x0F;
+pub const K_MURPHY_STATE_SNAPPING_BASE: u8 = 0x10;
+pub const K_MURPHY_STATE_SNAPPING_INFOTRON: u8 = 0x14;
+pub const K_MURPHY_STATE_PASSING_PORT: u8 = 0x18;
+const K_MURPHY_STATE_EATING_RED_DISK: u8 = 0x1C;
+pub const K_MURPHY_STATE_SNAPPING_RED_DISK: u8 = 0x20;
+const K_MURPHY_STATE_PUSHING_YELLOW_DISK: u8 = 0x24;
+const K_MURPHY_STATE_PUSHING_ORANGE_DISK_LEFT: u8 = 0x28;
+const K_MURPHY_STATE_PUSHING_ORANGE_DISK_RIGHT: u8 = 0x29;
+pub const K_MURPHY_STATE_RELEASING_RED_DISK: u8 = 0x2A;
+
+// State of the ports that change the level settings when Murphy goes through them
+pub const K_SPECIAL_PORT_STATE: u8 = 0x01;
+
+// Frames Murphy pushes against an object before it starts moving
+const K_MURPHY_PUSH_DELAY: u16 = 8;
+// Frames Space has to be held before a red disk is released
+const K_RED_DISK_RELEASE_DELAY: u16 = 0x40;
+// Frames the number of red disks stays in the game panel after it changed
+pub const K_RED_DISKS_DISPLAY_FRAMES: u8 = 0x46;
+// Planted red disk countdown while Murphy holds it, and once it's released
+const K_RED_DISK_HELD_COUNTDOWN: u8 = 1;
+const K_RED_DISK_RELEASED_COUNTDOWN: u8 = 2;
+
+// Explosions which Murphy can walk into, because they are about to disappear
+const K_MURPHY_EXPLOSION_WALKABLE_FRAME: u8 = 4;
+
+// Murphy starts yawning after ~13 seconds without input, and falls asleep after ~45 seconds
+pub const K_MURPHY_YAWN_DELAY: u16 = 0x01C0;
+pub const K_MURPHY_SLEEP_DELAY: u16 = 0x0640;
+const K_MURPHY_YAWN_FRAME_DURATION: u16 = 4;
+const K_MURPHY_SLEEP_FRAME_DURATION: u16 = 16;
+const K_MURPHY_YAWN_ANIMATION_INDEX: usize = 34;
+const K_MURPHY_SLEEP_LEFT_ANIMATION_INDEX: usize = 35;
+const K_MURPHY_SLEEP_RIGHT_ANIMATION_INDEX: usize = 36;
+
+// Indexes in K_MURPHY_ANIMATION_DESCRIPTOR, by direction (up, left, down, right). Vertical moves have
+// a variant for each side Murphy is looking at: (looking left, looking right).
+const K_MURPHY_MOVE_UP_ANIMATIONS: (usize, usize) = (0, 1);
+const K_MURPHY_MOVE_DOWN_ANIMATIONS: (usize, usize) = (3, 4);
+const K_MURPHY_MOVE_LEFT_ANIMATION: usize = 2;
+const K_MURPHY_MOVE_RIGHT_ANIMATION: usize = 5;
+const K_MURPHY_EXIT_ANIMATION: usize = 6;
+const K_MURPHY_EAT_BASE_UP_ANIMATIONS: (usize, usize) = (7, 8);
+const K_MURPHY_EAT_BASE_DOWN_ANIMATIONS: (usize, usize) = (10, 11);
+const K_MURPHY_EAT_BASE_LEFT_ANIMATION: usize = 9;
+const K_MURPHY_EAT_BASE_RIGHT_ANIMATION: usize = 12;
+const K_MURPHY_SNAP_BASE_ANIMATIONS: [usize; 4] = [13, 14, 15, 16];
+const K_MURPHY_EAT_INFOTRON_UP_ANIMATIONS: (usize, usize) = (17, 18);
+const K_MURPHY_EAT_INFOTRON_DOWN_ANIMATIONS: (usize, usize) = (20, 21);
+const K_MURPHY_EAT_INFOTRON_LEFT_ANIMATION: usize = 19;
+const K_MURPHY_EAT_INFOTRON_RIGHT_ANIMATION: usize = 22;
+const K_MURPHY_SNAP_INFOTRON_ANIMATIONS: [usize; 4] = [23, 24, 25, 26];
+const K_MURPHY_PUSH_ZONK_LEFT_ANIMATION: usize = 27;
+const K_MURPHY_PUSH_ZONK_RIGHT_ANIMATION: usize = 28;
+const K_MURPHY_PASS_PORT_ANIMATIONS: [usize; 4] = [29, 30, 31, 32];
+const K_MURPHY_EAT_RED_DISK_UP_ANIMATIONS: (usize, usize) = (33, 34);
+const K_MURPHY_EAT_RED_DISK_DOWN_ANIMATIONS: (usize, usize) = (36, 37);
+const K_MURPHY_EAT_RED_DISK_LEFT_ANIMATION: usize = 35;
+const K_MURPHY_EAT_RED_DISK_RIGHT_ANIMATION: usize = 38;
+const K_MURPHY_SNAP_RED_DISK_ANIMATIONS: [usize; 4] = [39, 40, 41, 42];
+const K_MURPHY_PUSH_YELLOW_DISK_ANIMATIONS: [usize; 4] = [43, 44, 45, 46];
+const K_MURPHY_PUSH_ORANGE_DISK_LEFT_ANIMATION: usize = 47;
+const K_MURPHY_PUSH_ORANGE_DISK_RIGHT_ANIMATION: usize = 48;
+const K_MURPHY_RELEASE_RED_DISK_ANIMATION: usize = 49;
+
+/// Returns the direction of the user input, and whether it's a snap (Space + direction)
+fn murphy_action_from_user_input(user_input: UserInput) -> Option<(Direction, bool)> {
+    match user_input {
+        UserInput::Up => Some((Direction::Up, false)),
+        UserInput::Left => Some((Direction::Left, false)),
+        UserInput::Down => Some((Direction::Down, false)),
+        UserInput::Right => Some((Direction::Right, false)),
+        UserInput::SpaceUp => Some((Direction::Up, true)),
+        UserInput::SpaceLeft => Some((Direction::Left, true)),
+        UserInput::SpaceDown => Some((Direction::Down, true)),
+        UserInput::SpaceRight => Some((Direction::Right, true)),
+        _ => None,
+    }
+}
+
+/// Tells if a port tile lets Murphy go through it in the given direction. Special ports have the
+/// same tile as the regular ones.
+fn can_port_be_passed(tile: u8, direction: Direction) -> bool {
+    let (one_way_port, two_way_port) = match direction {
+        Direction::Up => (LevelTileType::PortUp, LevelTileType::PortVertical),
+        Direction::Left => (LevelTileType::PortLeft, LevelTileType::PortHorizontal),
+        Direction::Down => (LevelTileType::PortDown, LevelTileType::PortVertical),
+        Direction::Right => (LevelTileType::PortRight, LevelTileType::PortHorizontal),
+    };
+    tile == one_way_port as u8
+        || tile == two_way_port as u8
+        || tile == LevelTileType::Port4Way as u8
+}
+
+/// Tells if Murphy can't go through the tile with the given word, without being hurt
+fn is_murphy_blocked_by(word: u16) -> bool {
+    let tile = (word & 0xFF) as u8;
+    word >> 8 == 0
+        || (tile >= LevelTileType::OrangeDisk as u8 && tile <= LevelTileType::PortUp as u8)
+}
+
+impl GameStates {
+    /// Updates Murphy for one frame: keeps playing the current action, or starts a new one from the
+    /// user input when Murphy is idle (updateMurphy).
+    pub fn update_murphy(&mut self) {
+        let location = self.g_murphy_location;
+        if self.tile_at(location) != LevelTileType::Murphy as u8 {
+            self.g_is_murphy_updated = false;
+            return;
+        }
+        self.g_is_murphy_updated = true;
+
+        if self.word_at(location) != LevelTileType::Murphy as u16 {
+            self.update_murphy_action();
+            return;
+        }
+
+        // With gravity, Murphy falls unless he stands in a port
+        let above_tile = self.tile_at(adjacent_position(location, Direction::Up));
+        let below = adjacent_position(location, Direction::Down);
+        let has_gravity = self.g_is_gravity_enabled != 0
+            && above_tile != LevelTileType::PortUp as u8
+            && above_tile != LevelTileType::PortVertical as u8
+            && above_tile != LevelTileType::Port4Way as u8
+            && self.word_at(below) == 0;
+
+        let user_input = self.g_current_user_input;
+        if user_input == UserInput::None {
+            self.g_previous_user_input_was_none = true;
+            if has_gravity {
+                self.try_murphy_move(Direction::Down);
+            } else {
+                self.g_murphy_yawn_and_sleep_counter =
+                    self.g_murphy_yawn_and_sleep_counter.saturating_add(1);
+            }
+            return;
+        }
+        self.g_murphy_yawn_and_sleep_counter = 0;
+
+        let action = if has_gravity {
+            // Murphy can only fight the gravity to eat a base
+            match murphy_action_from_user_input(user_input) {
+                Some((direction, false))
+                    if direction != Direction::Down
+                        && self.word_at(adjacent_position(location, direction))
+                            == LevelTileType::Base as u16 =>
+                {
+                    Some((direction, false))
+                }
+                _ => Some((Direction::Down, false)),
+            }
+        } else {
+            murphy_action_from_user_input(user_input)
+        };
+
+        let Some((direction, is_snap)) = action else {
+            if user_input == UserInput::SpaceOnly {
+                self.try_murphy_release_red_disk();
+            }
+            return;
+        };
+        self.g_previous_user_input_was_none = false;
+
+        if is_snap {
+            self.try_murphy_snap(direction);
+        } else {
+            self.try_murphy_move(direction);
+        }
+    }
+
+    /// Returns the frame of MOVING.DAT Murphy is showing when he is idle, if any, as
+    /// (index in K_MURPHY_ANIMATION_FRAME_COORDINATES, frame)
+    pub fn murphy_idle_frame(&self) -> Option<(usize, usize)> {
+        let counter = self.g_murphy_yawn_and_sleep_counter;
+        if counter >= K_MURPHY_SLEEP_DELAY {
+            let animation_index = if self.g_is_murphy_looking_left {
+                K_MURPHY_SLEEP_LEFT_ANIMATION_INDEX
+            } else {
+                K_MURPHY_SLEEP_RIGHT_ANIMATION_INDEX
+            };
+            let number_of_frames = K_MURPHY_ANIMATION_FRAME_COORDINATES[animation_index].len();
+            let frame = ((counter - K_MURPHY_SLEEP_DELAY) / K_MURPHY_SLEEP_FRAME_DURATION) as usize;
+            return Some((animation_index, frame.min(number_of_frames - 1)));
+        }
+
+        if counter >= K_MURPHY_YAWN_DELAY {
+            let number_of_frames =
+                K_MURPHY_ANIMATION_FRAME_COORDINATES[K_MURPHY_YAWN_ANIMATION_INDEX].len();
+            let frame = ((counter - K_MURPHY_YAWN_DELAY) / K_MURPHY_YAWN_FRAME_DURATION) as usize;
+            if frame < number_of_frames {
+                return Some((K_MURPHY_YAWN_ANIMATION_INDEX, frame));
+            }
+        }
+
+        None
+    }
+
+    fn try_murphy_move(&mut self, direction: Direction) {
+        if direction.is_horizontal() {
+            self.g_is_murphy_looking_left = direction == Direction::Left;
+        }
+
+        let location = self.g_murphy_location;
+        let target = adjacent_position(location, direction);
+        loop {
+            let word = self.word_at(target);
+            let tile = (word & 0xFF) as u8;
+
+            if word == 0 {
+                let animation_index = self.murphy_move_animation(
+                    direction,
+                    K_MURPHY_MOVE_UP_ANIMATIONS,
+                    K_MURPHY_MOVE_LEFT_ANIMATION,
+                    K_MURPHY_MOVE_DOWN_ANIMATIONS,
+                    K_MURPHY_MOVE_RIGHT_ANIMATION,
+                );
+                return self.start_murphy_walk(
+                    direction,
+                    K_MURPHY_MOVING_STATES[direction as usize],
+                    animation_index,
+                );
+            }
+            if word == LevelTileType::Base as u16 {
+                return self.start_murphy_eat_base(direction);
+            }
+            if tile == LevelTileType::Bug as u8 {
+                // Bugs are bases, which are deadly while they spark
+                if is_bug_active(self.g_current_level_state[target].state) {
+                    return self.detonate(location);
+                }
+                return self.start_murphy_eat_base(direction);
+            }
+            if word == LevelTileType::Infotron as u16 {
+                self.queue_sound_effect(SoundEffect::Infotron);
+                let animation_index = self.murphy_move_animation(
+                    direction,
+                    K_MURPHY_EAT_INFOTRON_UP_ANIMATIONS,
+                    K_MURPHY_EAT_INFOTRON_LEFT_ANIMATION,
+                    K_MURPHY_EAT_INFOTRON_DOWN_ANIMATIONS,
+                    K_MURPHY_EAT_INFOTRON_RIGHT_ANIMATION,
+                );
+                return self.start_murphy_walk(
+                    direction,
+                    K_MURPHY_EATING_INFOTRON_STATES[direction as usize],
+                    animation_index,
+                );
+            }
+            if word == LevelTileType::Exit as u16 {
+                return self.try_murphy_exit();
+            }
+            if word == LevelTileType::Zonk as u16 && direction.is_horizontal() {
+                return self.try_murphy_push_zonk(direction);
+            }
+            if tile == LevelTileType::Terminal as u8 {
+                return self.use_terminal();
+            }
+            if can_port_be_passed(tile, direction) {
+                return self.try_murphy_pass_port(direction);
+            }
+            if tile == LevelTileType::RedDisk as u8 {
+                return self.start_murphy_eat_red_disk(direction);
+            }
+            if tile == LevelTileType::YellowDisk as u8 {
+                return self.try_murphy_push_yellow_disk(direction);
+            }
+            if word == LevelTileType::OrangeDisk as u16 && direction.is_horizontal() {
+                return self.try_murphy_push_orange_disk(direction);
+            }
+            if !self.move_kills_murphy(target, word, direction) {
+                return;
+            }
+        }
+    }
+
+    /// Handles Murphy moving into a tile he can't go through (checkMurphyMovementToPosition).
+    /// Returns true when the tile was freed and the move can be tried again.
+    fn move_kills_murphy(&mut self, position: usize, word: u16, direction: Direction) -> bool {
+        if word == 0xFFFF || word == 0xAAAA || word >> 8 == 0 {
+            return false;
+        }
+
+        let tile = (word & 0xFF) as u8;
+        let state = (word >> 8) as u8;
+        if tile == LevelTileType::Zonk as u8 {
+            // Zonks rolling away from Murphy don't hurt him
+            let phase = state & 0xF0;
+            let is_harmless = match direction {
+                Direction::Left => [0x20, 0x40, 0x50, 0x70].contains(&phase),
+                Direction::Right => [0x30, 0x40, 0x60, 0x70].contains(&phase),
+                _ => false,
+            };
+            if !is_harmless {
+                self.detonate(position);
+            }
+            return false;
+        }
+        if tile == LevelTileType::Explosion as u8 {
+            if state & K_INFOTRON_EXPLOSION_FLAG != 0 || state < K_MURPHY_EXPLOSION_WALKABLE_FRAME {
+                self.detonate(position);
+                return false;
+            }
+            self.set_word(position, 0);
+            return true;
+        }
+        if is_murphy_blocked_by(word) {
+            return false;
+        }
+
+        self.detonate(position);
+        false
+    }
+
+    fn try_murphy_snap(&mut self, direction: Direction) {
+        if direction.is_horizontal() {
+            self.g_is_murphy_looking_left = direction == Direction::Left;
+        }
+
+        let location = self.g_murphy_location;
+        let target = adjacent_position(location, direction);
+        let word = self.word_at(target);
+        let tile = (word & 0xFF) as u8;
+
+        let (state, animation_index) = if word == LevelTileType::Base as u16
+            || (tile == LevelTileType::Bug as u8
+                && !is_bug_active(self.g_current_level_state[target].state))
+        {
+            self.queue_sound_effect(SoundEffect::Base);
+            (
+                K_MURPHY_STATE_SNAPPING_BASE,
+                K_MURPHY_SNAP_BASE_ANIMATIONS[direction as usize],
+            )
+        } else if tile == LevelTileType::Bug as u8 {
+            return self.detonate(location);
+        } else if word == LevelTileType::Infotron as u16 {
+            self.queue_sound_effect(SoundEffect::Infotron);
+            // Keeps the infotron from falling while Murphy is snapping it
+            self.set_state(target, 0xFF);
+            (
+                K_MURPHY_STATE_SNAPPING_INFOTRON,
+                K_MURPHY_SNAP_INFOTRON_ANIMATIONS[direction as usize],
+            )
+        } else if tile == LevelTileType::Terminal as u8 {
+            return self.use_terminal();
+        } else if tile == LevelTileType::RedDisk as u8 {
+            (
+                K_MURPHY_STATE_SNAPPING_RED_DISK,
+                K_MURPHY_SNAP_RED_DISK_ANIMATIONS[direction as usize],
+            )
+        } else {
+            return;
+        };
+
+        self.set_state(location, state + direction as u8);
+        self.start_murphy_animation(animation_index, location);
+    }
+
+    fn start_murphy_eat_base(&mut self, direction: Direction) {
+        self.queue_sound_effect(SoundEffect::Base);
+        let animation_index = self.murphy_move_animation(
+            direction,
+            K_MURPHY_EAT_BASE_UP_ANIMATIONS,
+            K_MURPHY_EAT_BASE_LEFT_ANIMATION,
+            K_MURPHY_EAT_BASE_DOWN_ANIMATIONS,
+            K_MURPHY_EAT_BASE_RIGHT_ANIMATION,
+        );
+        self.start_murphy_walk(
+            direction,
+            K_MURPHY_EATING_BASE_STATES[direction as usize],
+            animation_index,
+        );
+    }
+
+    /// Murphy picks up red disks on his side right away, and the ones above or below him from his
+    /// own tile
+    fn start_murphy_eat_red_disk(&mut self, direction: Direction) {
+        let location = self.g_murphy_location;
+        let state = K_MURPHY_STATE_EATING_RED_DISK + direction as u8;
+        let animation_index = self.murphy_move_animation(
+            direction,
+            K_MURPHY_EAT_RED_DISK_UP_ANIMATIONS,
+            K_MURPHY_EAT_RED_DISK_LEFT_ANIMATION,
+            K_MURPHY_EAT_RED_DISK_DOWN_ANIMATIONS,
+            K_MURPHY_EAT_RED_DISK_RIGHT_ANIMATION,
+        );
+        if direction.is_horizontal() {
+            return self.start_murphy_walk(direction, state, animation_index);
+        }
+
+        self.set_word(adjacent_position(location, direction), K_MURPHY_TRAIL_WORD);
+        self.set_state(location, state);
+        self.start_murphy_animation(animation_index, location);
+    }
+
+    fn try_murphy_exit(&mut self) {
+        if self.g_number_of_remaining_infotrons != 0 {
+            return;
+        }
+
+        let location = self.g_murphy_location;
+        self.queue_sound_effect(SoundEffect::Exit);
+        self.g_is_game_running = false;
+        self.g_level_failed = false;
+        self.g_current_player_level_state = PlayerLevelState::Completed;
+        self.g_quit_level_countdown = K_QUIT_LEVEL_COUNTDOWN;
+        self.set_state(location, K_MURPHY_STATE_EXITING);
+        self.start_murphy_animation(K_MURPHY_EXIT_ANIMATION, location);
+    }
+
+    fn try_murphy_push_zonk(&mut self, direction: Direction) {
+        let pushed_location = adjacent_position(self.g_murphy_location, direction);
+        let destination = adjacent_position(pushed_location, direction);
+        if self.word_at(destination) != 0 {
+            return;
+        }
+
+        let (state, animation_index) = if direction == Direction::Left {
+            (
+                K_MURPHY_STATE_PUSHING_ZONK_LEFT,
+                K_MURPHY_PUSH_ZONK_LEFT_ANIMATION,
+            )
+        } else {
+            // Zonks can't be pushed to the right while they are about to fall
+            if self.word_at(adjacent_position(pushed_location, Direction::Down)) == 0 {
+                return;
+            }
+            (
+                K_MURPHY_STATE_PUSHING_ZONK_RIGHT,
+                K_MURPHY_PUSH_ZONK_RIGHT_ANIMATION,
+            )
+        };
+        self.start_murphy_push(destination, state, animation_index, LevelTileType::Zonk);
+    }
+
+    fn try_murphy_push_orange_disk(&mut self, direction: Direction) {
+        let pushed_location = adjacent_position(self.g_murphy_location, direction);
+        let destination = adjacent_position(pushed_location, direction);
+        if self.word_at(destination) != 0 {
+            return;
+        }
+
+        let (state, animation_index) = if direction == Direction::Left {
+            (
+                K_MURPHY_STATE_PUSHING_ORANGE_DISK_LEFT,
+                K_MURPHY_PUSH_ORANGE_DISK_LEFT_ANIMATION,
+            )
+        } else {
+            if self.word_at(adjacent_position(pushed_location, Direction::Down)) == 0 {
+                return;
+            }
+            (
+                K_MURPHY_STATE_PUSHING_ORANGE_DISK_RIGHT,
+                K_MURPHY_PUSH_ORANGE_DISK_RIGHT_ANIMATION,
+            )
+        };
+        self.start_murphy_push(
+            destination,
+            state,
+            animation_index,
+            LevelTileType::OrangeDisk,
+        );
+    }
+
+    fn try_murphy_push_yellow_disk(&mut self, direction: Direction) {
+        let location = self.g_murphy_location;
+        let destination = adjacent_position(adjacent_position(location, direction), direction);
+        if self.word_at(destination) != 0 {
+            return;
+        }
+
+        self.start_murphy_push(
+            destination,
+            K_MURPHY_STATE_PUSHING_YELLOW_DISK + direction as u8,
+            K_MURPHY_PUSH_YELLOW_DISK_ANIMATIONS[direction as usize],
+            LevelTileType::YellowDisk,
+        );
+    }
+
+    /// Reserves the tile the object is pushed into. The object only moves once Murphy pushed it
+    /// for K_MURPHY_PUSH_DELAY frames.
+    fn start_murphy_push(
+        &mut self,
+        destination: usize,
+        state: u8,
+        animation_index: usize,
+        pushed_tile: LevelTileType,
+    ) {
+        let location = self.g_murphy_location;
+        self.set_word(destination, (pushed_tile as u16) << 8);
+        self.set_state(location, state);
+        self.g_murphy_counter_to_start_push_animation = K_MURPHY_PUSH_DELAY;
+        self.start_murphy_animation(animation_index, location);
+        self.update_murphy_action();
+    }
+
+    fn try_murphy_pass_port(&mut self, direction: Direction) {
+        let location = self.g_murphy_location;
+        let port_location = adjacent_position(location, direction);
+        let destination = adjacent_position(port_location, direction);
+        if self.word_at(destination) != 0 {
+            return;
+        }
+
+        if self.g_current_level_state[port_location].state == K_SPECIAL_PORT_STATE {
+            self.apply_special_port(port_location);
+        }
+
+        self.set_word(destination, K_MURPHY_TRAIL_WORD);
+        self.g_is_murphy_going_through_portal = 1;
+        self.set_state(location, K_MURPHY_STATE_PASSING_PORT + direction as u8);
+        self.start_murphy_animation(K_MURPHY_PASS_PORT_ANIMATIONS[direction as usize], location);
+    }
+
+    /// Applies the settings of the special port at the given position (updateSpecialPort)
+    fn apply_special_port(&mut self, position: usize) {
+        let number_of_special_ports =
+            (self.g_number_of_special_ports as usize).min(K_LEVEL_MAX_NUMBER_OF_SPECIAL_PORTS);
+        // Special port positions are stored as offsets in the original level memory, which used 2
+        // bytes per tile
+        let Some(port_info) = self.g_current_level.special_ports_info[..number_of_special_ports]
+            .iter()
+            .find(|port_info| port_info.position as usize / 2 == position)
+        else {
+            return;
+        };
+
+        self.g_is_gravity_enabled = port_info.gravity;
+        self.g_are_zonks_frozen = port_info.freeze_zonks;
+        self.g_are_enemies_frozen = port_info.freeze_enemies == 1;
+    }
+
+    /// Moves Murphy to the next tile right away, and leaves his trail in the tile he leaves
+    fn start_murphy_walk(&mut self, direction: Direction, state: u8, animation_index: usize) {
+        let location = self.g_murphy_location;
+        let destination = adjacent_position(location, direction);
+
+        self.set_word(location, K_MURPHY_TRAIL_WORD);
+        self.set_tile(destination, LevelTileType::Murphy, state);
+        self.g_murphy_previous_location = location as i32;
+        self.g_murphy_location = destination;
+        self.start_murphy_animation(animation_index, destination);
+    }
+
+    fn murphy_move_animation(
+        &self,
+        direction: Direction,
+        up_animations: (usize, usize),
+        left_animation: usize,
+        down_animations: (usize, usize),
+        right_animation: usize,
+    ) -> usize {
+        let pick = |(looking_left, looking_right): (usize, usize)| {
+            if self.g_is_murphy_looking_left {
+                looking_left
+            } else {
+                looking_right
+            }
+        };
+        match direction {
+            Direction::Up => pick(up_animations),
+            Direction::Left => left_animation,
+            Direction::Down => pick(down_animations),
+            Direction::Right => right_animation,
+        }
+    }
+
+    fn try_murphy_release_red_disk(&mut self) {
+        if self.g_number_of_remaining_red_disks == 0
+            || self.g_planted_red_disk_countdown != 0
+            || !self.g_previous_user_input_was_none
+        {
+            return;
+        }
+
+        let location = self.g_murphy_location;
+        self.set_state(location, K_MURPHY_STATE_RELEASING_RED_DISK);
+        self.g_murphy_counter_to_start_push_animation = K_RED_DISK_RELEASE_DELAY;
+        self.g_planted_red_disk_countdown = K_RED_DISK_HELD_COUNTDOWN;
+        self.g_planted_red_disk_position = location as u16;
+        self.start_murphy_animation(K_MURPHY_RELEASE_RED_DISK_ANIMATION, location);
+    }
+
+    /// Murphy holds the red disk until Space has been held long enough, or gives up if it's released
+    fn update_murphy_releasing_red_disk(&mut self) {
+        let location = self.g_murphy_location;
+        if self.g_murphy_counter_to_start_push_animation == 0 {
+            self.set_state(location, 0);
+            self.g_planted_red_disk_countdown = K_RED_DISK_RELEASED_COUNTDOWN;
+            self.g_number_of_remaining_red_disks =
+                self.g_number_of_remaining_red_disks.saturating_sub(1);
+            self.g_additional_info_in_game_panel_frame_counter = K_RED_DISKS_DISPLAY_FRAMES;
+            return;
+        }
+
+        self.g_murphy_counter_to_start_push_animation -= 1;
+        if self.g_current_user_input != UserInput::SpaceOnly {
+            self.set_state(location, 0);
+            self.g_planted_red_disk_countdown = 0;
+        }
+    }
+
+    fn start_murphy_animation(&mut self, animation_index: usize, anchor_location: usize) {
+        self.g_current_murphy_animation = K_MURPHY_ANIMATION_DESCRIPTOR[animation_index];
+        self.g_current_murphy_animation.current_frame = 0;
+        self.g_murphy_animation_anchor_location = anchor_location;
+        self.move_murphy_position();
+    }
+
+    fn move_murphy_position(&mut self) {
+        let animation = &self.g_current_murphy_animation;
+        self.g_murphy_position_x = self
+            .g_murphy_position_x
+            .wrapping_add_signed(animation.speed_x as isize);
+        self.g_murphy_position_y = self
+            .g_murphy_position_y
+            .wrapping_add_signed(animation.speed_y as isize);
+    }
+
+    /// Keeps playing Murphy's current action
+    fn update_murphy_action(&mut self) {
+        let location = self.g_murphy_location;
+        let state = self.g_current_level_state[location].state;
+        if state == K_MURPHY_STATE_RELEASING_RED_DISK {
+            return self.update_murphy_releasing_red_disk();
+        }
+        if self.g_murphy_counter_to_start_push_animation != 0 && !self.update_murphy_push(state) {
+            return;
+        }
+
+        let animation = &mut self.g_current_murphy_animation;
+        let number_of_frames =
+            K_MURPHY_ANIMATION_FRAME_COORDINATES[animation.animation_index as usize].len() as u16;
+        if animation.current_frame + 1 >= number_of_frames {
+            return;
+        }
+        animation.current_frame += 1;
+        animation.animation_coordinates_offset = animation
+            .animation_coordinates_offset
+            .wrapping_add(animation.animation_coordinates_offset_increment);
+        self.move_murphy_position();
+
+        if self.g_current_murphy_animation.current_frame + 1 == number_of_frames {
+            self.finish_murphy_action(state);
+        }
+    }
+
+    /// Counts down the frames Murphy pushes an object before it moves. The push is cancelled when
+    /// the direction key is released or the object started moving on its own. Returns true when
+    /// the object starts moving.
+    fn update_murphy_push(&mut self, state: u8) -> bool {
+        let location = self.g_murphy_location;
+        let (direction, resting_word) = match state {
+            K_MURPHY_STATE_PUSHING_ZONK_LEFT => (Direction::Left, LevelTileType::Zonk as u16),
+            K_MURPHY_STATE_PUSHING_ZONK_RIGHT => (Direction::Right, LevelTileType::Zonk as u16),
+            K_MURPHY_STATE_PUSHING_ORANGE_DISK_LEFT => {
+                (Direction::Left, LevelTileType::OrangeDisk as u16)
+            }
+            K_MURPHY_STATE_PUSHING_ORANGE_DISK_RIGHT => {
+                (Direction::Right, LevelTileType::OrangeDisk as u16)
+            }
+            s if s & 0xFC == K_MURPHY_STATE_PUSHING_YELLOW_DISK => (
+                Direction::from_state(state),
+                LevelTileType::YellowDisk as u16,
+            ),
+            // The counter is left over from a red disk release
+            _ => return true,
+        };
+        let key = match direction {
+            Direction::Up => UserInput::Up,
+            Direction::Left => UserInput::Left,
+            Direction::Down => UserInput::Down,
+            Direction::Right => UserInput::Right,
+        };
+
+        let pushed_location = adjacent_position(location, direction);
+        if self.g_current_user_input != key || self.word_at(pushed_location) != resting_word {
+            self.g_murphy_counter_to_start_push_animation = 0;
+            self.set_word(location, LevelTileType::Murphy as u16);
+            self.set_word(pushed_location, resting_word);
+            self.clear_tile_unless_exploding(adjacent_position(pushed_location, direction));
+            return false;
+        }
+
+        self.g_murphy_counter_to_start_push_animation -= 1;
+        if self.g_murphy_counter_to_start_push_animation != 0 {
+            return false;
+        }
+        self.queue_sound_effect(SoundEffect::Push);
+        true
+    }
+
+    /// Ends Murphy's current action, once its animation is over
+    fn finish_murphy_action(&mut self, state: u8) {
+        if state == K_MURPHY_STATE_EXITING {
+            return;
+        }
+
+        let location = self.g_murphy_location;
+        let direction = Direction::from_state(state);
+        let target = adjacent_position(location, direction);
+        self.set_state(location, 0);
+
+        let is_move = K_MURPHY_MOVING_STATES.contains(&state)
+            || K_MURPHY_EATING_BASE_STATES.contains(&state)
+            || K_MURPHY_EATING_INFOTRON_STATES.contains(&state);
+        if is_move {
+            if K_MURPHY_EATING_INFOTRON_STATES.contains(&state) {
+                self.g_number_of_remaining_infotrons =
+                    self.g_number_of_remaining_infotrons.saturating_sub(1);
+            }
+            // Eating a base on the left uses the state of moving left
+            let direction = match state {
+                0x01 | 0x05 | 0x09 => Direction::Up,
+                0x02 | 0x0A => Direction::Left,
+                0x03 | 0x07 | 0x0B => Direction::Down,
+                _ => Direction::Right,
+            };
+            self.leave_murphy_trail(location, direction);
+            return self.update_murphy_tile_position();
+        }
+
+        match state {
+            K_MURPHY_STATE_PUSHING_ZONK_LEFT
+            | K_MURPHY_STATE_PUSHING_ZONK_RIGHT
+            | K_MURPHY_STATE_PUSHING_ORANGE_DISK_LEFT
+            | K_MURPHY_STATE_PUSHING_ORANGE_DISK_RIGHT => {
+                let direction = if state == K_MURPHY_STATE_PUSHING_ZONK_LEFT
+                    || state == K_MURPHY_STATE_PUSHING_ORANGE_DISK_LEFT
+                {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                };
+                let pushed_tile = if state == K_MURPHY_STATE_PUSHING_ZONK_LEFT
+                    || state == K_MURPHY_STATE_PUSHING_ZONK_RIGHT
+                {
+                    LevelTileType::Zonk
+                } else {
+                    LevelTileType::OrangeDisk
+                };
+                self.finish_murphy_push(direction, pushed_tile);
+            }
+            s if s & 0xFC == K_MURPHY_STATE_PUSHING_YELLOW_DISK => {
+                self.finish_murphy_push(direction, LevelTileType::YellowDisk);
+            }
+            s if s & 0xFC == K_MURPHY_STATE_SNAPPING_BASE
+                || s & 0xFC == K_MURPHY_STATE_SNAPPING_INFOTRON
+                || s & 0xFC == K_MURPHY_STATE_SNAPPING_RED_DISK =>
+            {
+                if s & 0xFC == K_MURPHY_STATE_SNAPPING_INFOTRON {
+                    self.g_number_of_remaining_infotrons =
+                        self.g_number_of_remaining_infotrons.saturating_sub(1);
+                } else if s & 0xFC == K_MURPHY_STATE_SNAPPING_RED_DISK {
+                    self.collect_red_disk();
+                }
+                if direction == Direction::Up {
+                    self.clear_tile_unless_exploding(target);
+                } else {
+                    self.adjust_above_murphy(target);
+                }
+            }
+            s if s & 0xFC == K_MURPHY_STATE_PASSING_PORT => {
+                let destination = adjacent_position(target, direction);
+                self.clear_tile_unless_exploding(location);
+                self.set_tile(destination, LevelTileType::Murphy, 0);
+                self.g_is_murphy_going_through_portal = 0;
+                self.g_murphy_previous_location = location as i32;
+                self.g_murphy_location = destination;
+            }
+            s if s & 0xFC == K_MURPHY_STATE_EATING_RED_DISK => {
+                self.collect_red_disk();
+                match direction {
+                    Direction::Up | Direction::Down => {
+                        self.set_tile(target, LevelTileType::Murphy, 0);
+                        self.g_murphy_previous_location = location as i32;
+                        self.g_murphy_location = target;
+                        if direction == Direction::Up {
+                            self.adjust_above_murphy(location);
+                        } else {
+                            self.clear_tile_unless_exploding(location);
+                        }
+                    }
+                    _ => self.leave_murphy_trail(location, direction),
+                }
+            }
+            _ => {}
+        }
+
+        self.update_murphy_tile_position();
+    }
+
+    fn finish_murphy_push(&mut self, direction: Direction, pushed_tile: LevelTileType) {
+        let location = self.g_murphy_location;
+        let pushed_location = adjacent_position(location, direction);
+        let destination = adjacent_position(pushed_location, direction);
+
+        self.clear_tile_unless_exploding(location);
+        self.set_tile(pushed_location, LevelTileType::Murphy, 0);
+        self.set_tile(destination, pushed_tile, 0);
+        self.g_murphy_previous_location = location as i32;
+        self.g_murphy_location = pushed_location;
+
+        // A zonk pushed onto a snik snak, or onto an enemy leaving the tile below, crushes it. Like
+        // in the original game, only the tile byte of the marker word is compared, whatever the
+        // direction the enemy leaves to.
+        if pushed_tile == LevelTileType::Zonk {
+            let below = adjacent_position(destination, Direction::Down);
+            let below_tile = self.tile_at(below);
+            if below_tile == LevelTileType::SnikSnak as u8 || below_tile == K_ENEMY_LEAVING_TILE {
+                self.detonate(below);
+            }
+        }
+    }
+
+    /// Frees the tile Murphy left with a move in the given direction
+    fn leave_murphy_trail(&mut self, location: usize, direction: Direction) {
+        match direction {
+            Direction::Up => self.adjust_above_murphy(adjacent_position(location, Direction::Down)),
+            Direction::Left => {
+                self.adjust_above_murphy(adjacent_position(location, Direction::Right))
+            }
+            Direction::Down => {
+                self.clear_tile_unless_exploding(adjacent_position(location, Direction::Up))
+            }
+            Direction::Right => {
+                self.adjust_above_murphy(adjacent_position(location, Direction::Left))
+            }
+        }
+    }
+
+    /// Empties a tile Murphy left, and makes the objects above it fall or roll into it right away
+    fn adjust_above_murphy(&mut self, position: usize) {
+        self.clear_tile_unless_exploding(position);
+
+        let above = adjacent_position(position, Direction::Up);
+        let above_word = self.word_at(above);
+        let zonk = LevelTileType::Zonk as u16;
+        let infotron = LevelTileType::Infotron as u16;
+        if above_word == zonk || above_word == infotron {
+            self.set_state(above, 0x40);
+            return;
+        }
+        if above_word != 0 && above_word != K_FALLING_DESTINATION_WORD {
+            return;
+        }
+
+        let is_rolling_object = |word: u16| word == zonk || word == infotron;
+        let can_roll_from =
+            |word: u16| word == zonk || word == infotron || word == LevelTileType::Chip as u16;
+        let above_left = above.wrapping_sub(1);
+        let above_right = above + 1;
+        if is_rolling_object(self.word_at(above_left))
+            && can_roll_from(self.word_at(position.wrapping_sub(1)))
+        {
+            self.set_state(above_left, 0x60);
+            self.set_word(above, K_ROLLING_DESTINATION_WORD);
+        } else if is_rolling_object(self.word_at(above_right))
+            && can_roll_from(self.word_at(position + 1))
+        {
+            self.set_state(above_right, 0x50);
+            self.set_word(above, K_ROLLING_DESTINATION_WORD);
+        }
+    }
+
+    fn collect_red_disk(&mut self) {
+        self.g_number_of_remaining_red_disks =
+            self.g_number_of_remaining_red_disks.saturating_add(1);
+        self.g_additional_info_in_game_panel_frame_counter = K_RED_DISKS_DISPLAY_FRAMES;
+    }
+
+    fn update_murphy_tile_position(&mut self) {
+        let location = self.g_murphy_location;
+        self.g_murphy_tile_x = location % K_LEVEL_WIDTH;
+        self.g_murphy_tile_y = location / K_LEVEL_WIDTH;
+        self.g_murphy_position_x = self.g_murphy_tile_x * K_TILE_SIZE;
+        self.g_murphy_position_y = self.g_murphy_tile_y * K_TILE_SIZE;
+    }
+}
@@ -0,0 +1,191 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Player list files (PLAYER.LST). With the original level sets of 111 levels, they are written like
+// in the original game: the players one after the other, in entries of 128 bytes. Level sets with
+// another number of levels use an extended format, starting with a header:
+//   "PLX ", the version (1) and the number of levels, as 16 bits little endian values
+// followed by the players, in entries of player_entry_size(number of levels) bytes.
+
+use crate::game::globals::*;
+
+const K_EXTENDED_PLAYER_LIST_MAGIC: &[u8; 4] = b"PLX ";
+const K_EXTENDED_PLAYER_LIST_VERSION: u16 = 1;
+const K_EXTENDED_PLAYER_LIST_HEADER_LENGTH: usize = 8;
+
+/// Reads the players of a player list file, in either format. Players get a state for each of the
+/// given number of levels, levels they didn't know about being not completed. Players saved with
+/// more levels keep the progress on the levels the level set doesn't have.
+pub fn read_player_list(data: &[u8], number_of_levels: usize) -> Result<Vec<PlayerEntry>, String> {
+    let (player_data, number_of_saved_levels) = if data.starts_with(K_EXTENDED_PLAYER_LIST_MAGIC) {
+        if data.len() < K_EXTENDED_PLAYER_LIST_HEADER_LENGTH {
+            return Err(String::from("The player list is truncated"));
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != K_EXTENDED_PLAYER_LIST_VERSION {
+            return Err(format!(
+                "The player list version {} is not supported",
+                version
+            ));
+        }
+        let number_of_saved_levels = u16::from_le_bytes([data[6], data[7]]) as usize;
+        (
+            &data[K_EXTENDED_PLAYER_LIST_HEADER_LENGTH..],
+            number_of_saved_levels,
+        )
+    } else {
+        (data, K_NUMBER_OF_LEVELS)
+    };
+
+    // Like the original game, a file with less players only fills the first entries
+    let players = player_data
+        .chunks_exact(player_entry_size(number_of_saved_levels))
+        .take(K_NUMBER_OF_PLAYERS)
+        .map(|entry_data| {
+            let mut player = PlayerEntry::from(entry_data, number_of_saved_levels);
+            player.level_state.resize(
+                number_of_levels.max(number_of_saved_levels),
+                PlayerLevelState::NotCompleted,
+            );
+            player
+        })
+        .collect();
+    Ok(players)
+}
+
+/// Writes the players into a player list file for the given number of levels, or more when players
+/// have progress on more levels, so that playing a smaller level set doesn't lose it. The file is in
+/// the original format when it holds 111 levels, and in the extended one otherwise.
+pub fn write_player_list(
+    players: &[PlayerEntry],
+    number_of_levels: usize,
+) -> Result<Vec<u8>, String> {
+    let number_of_levels = players
+        .iter()
+        .map(|player| player.level_state.len())
+        .fold(number_of_levels, usize::max);
+
+    let mut data = Vec::new();
+    if number_of_levels != K_NUMBER_OF_LEVELS {
+        let number_of_saved_levels = u16::try_from(number_of_levels).map_err(|_| {
+            format!(
+                "A player list can't hold the progress of {} levels",
+                number_of_levels
+            )
+        })?;
+        data.extend_from_slice(K_EXTENDED_PLAYER_LIST_MAGIC);
+        data.extend_from_slice(&K_EXTENDED_PLAYER_LIST_VERSION.to_le_bytes());
+        data.extend_from_slice(&number_of_saved_levels.to_le_bytes());
+    }
+
+    for player in players {
+        let mut player = player.clone();
+        player
+            .level_state
+            .resize(number_of_levels, PlayerLevelState::NotCompleted);
+        data.extend_from_slice(&player.to_raw());
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn player_with_progress(number_of_levels: usize) -> PlayerEntry {
+        let mut player = PlayerEntry::new(number_of_levels);
+        player.name = String::from("MURPHY  ");
+        player.hours = 1;
+        player.minutes = 2;
+        player.seconds = 3;
+        player.level_state[0] = PlayerLevelState::Completed;
+        player.level_state[1] = PlayerLevelState::Skipped;
+        player.next_level_to_play = number_of_levels as u16;
+        player
+    }
+
+    #[test]
+    fn shipped_player_list_is_written_back_identically() {
+        let player_list_path = format!(
+            "{}/{}/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            RESSOURCES_PATH,
+            G_PLAYERS_LST_FILENAME
+        );
+        let data = fs::read(player_list_path).unwrap();
+        let players = read_player_list(&data, K_NUMBER_OF_LEVELS).unwrap();
+        assert_eq!(players.len(), K_NUMBER_OF_PLAYERS);
+        assert_eq!(
+            write_player_list(&players, K_NUMBER_OF_LEVELS).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn original_level_sets_use_the_original_format() {
+        let players = vec![player_with_progress(K_NUMBER_OF_LEVELS); K_NUMBER_OF_PLAYERS];
+        let data = write_player_list(&players, K_NUMBER_OF_LEVELS).unwrap();
+        // Entries of the original game are 128 bytes long
+        assert_eq!(data.len(), K_NUMBER_OF_PLAYERS * 128);
+        assert_eq!(&data[..K_PLAYER_NAME_LENGTH], b"MURPHY  ");
+    }
+
+    #[test]
+    fn players_of_large_level_sets_are_read_back() {
+        let number_of_levels = 300;
+        let players = vec![player_with_progress(number_of_levels); K_NUMBER_OF_PLAYERS];
+        let data = write_player_list(&players, number_of_levels).unwrap();
+        assert!(data.starts_with(K_EXTENDED_PLAYER_LIST_MAGIC));
+
+        let players = read_player_list(&data, number_of_levels).unwrap();
+        assert_eq!(players.len(), K_NUMBER_OF_PLAYERS);
+        assert_eq!(players[0].name, "MURPHY  ");
+        assert_eq!(players[0].level_state.len(), number_of_levels);
+        assert!(players[0].level_state[1] == PlayerLevelState::Skipped);
+        assert_eq!(players[0].next_level_to_play, 300);
+        assert_eq!(players[0].seconds, 3);
+    }
+
+    #[test]
+    fn players_are_adapted_to_the_number_of_levels_of_the_level_set() {
+        let players = vec![player_with_progress(20); K_NUMBER_OF_PLAYERS];
+        let data = write_player_list(&players, 20).unwrap();
+
+        let players = read_player_list(&data, K_NUMBER_OF_LEVELS).unwrap();
+        assert_eq!(players[0].level_state.len(), K_NUMBER_OF_LEVELS);
+        assert!(players[0].level_state[0] == PlayerLevelState::Completed);
+        assert!(players[0].level_state[20] == PlayerLevelState::NotCompleted);
+    }
+
+    #[test]
+    fn progress_on_levels_missing_from_the_level_set_is_kept() {
+        let mut player = player_with_progress(K_NUMBER_OF_LEVELS);
+        player.level_state[110] = PlayerLevelState::Completed;
+        let players = vec![player; K_NUMBER_OF_PLAYERS];
+        let data = write_player_list(&players, K_NUMBER_OF_LEVELS).unwrap();
+
+        // Playing a level set of 20 levels saves the player list with them
+        let players = read_player_list(&data, 20).unwrap();
+        let data = write_player_list(&players, 20).unwrap();
+
+        let players = read_player_list(&data, K_NUMBER_OF_LEVELS).unwrap();
+        assert_eq!(players[0].level_state.len(), K_NUMBER_OF_LEVELS);
+        assert!(players[0].level_state[1] == PlayerLevelState::Skipped);
+        assert!(players[0].level_state[110] == PlayerLevelState::Completed);
+    }
+}
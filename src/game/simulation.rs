@@ -0,0 +1,424 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Per-frame simulation of a level. Everything here only works on GameStates, so that the
+// simulation can run without any SDL context.
+
+use crate::game::game_states::GameStates;
+use crate::game::globals::*;
+use crate::game::graphics::{K_PANEL_BITMAP_HEIGHT, K_TILE_SIZE};
+use crate::game::keyboard::UserInput;
+use crate::game::level::Level;
+use crate::game::murphy::K_SPECIAL_PORT_STATE;
+use crate::game::sounds::SoundEffect;
+
+// The original game runs at 35 frames per second, the game time is derived from the frame count
+pub const K_GAME_FRAMES_PER_SECOND: u8 = 35;
+
+// The simulation works on words made of a tile and its state, with the state in the high byte, like
+// the original game did. Some words of empty tiles are markers reserving the tile for an object
+// that is moving into it or leaving it.
+pub const K_MURPHY_TRAIL_WORD: u16 = 0x0300; // Murphy is leaving the tile
+pub const K_ROLLING_DESTINATION_WORD: u16 = 0x8888; // a zonk or infotron is about to roll into it
+pub const K_FALLING_DESTINATION_WORD: u16 = 0x9999; // a zonk or infotron is about to fall into it
+pub const K_ROLLING_SOURCE_WORD: u16 = 0xAAAA; // a zonk or infotron is rolling out of it
+pub const K_OBJECT_LEAVING_WORD: u16 = 0xFFFF; // an object is leaving the tile
+pub const K_ENEMY_LEAVING_TILE: u8 = 0xBB; // an enemy is leaving the tile, state is direction + 1
+
+// Frames Murphy's death or exit let the game run before leaving the level
+pub const K_QUIT_LEVEL_COUNTDOWN: u16 = 0x40;
+
+// A planted red disk explodes when its countdown reaches this value
+const K_PLANTED_RED_DISK_EXPLOSION_COUNTDOWN: u8 = 0x28;
+
+// Explosion tile as stored in the level files
+const K_LEVEL_FILE_EXPLOSION_TILE: u8 = 0xF1;
+
+// States of the snik snaks and electrons when the level starts, see convert_to_easy_tiles
+const K_ENEMY_INITIAL_TURNING_STATE: u8 = 0x01;
+const K_ENEMY_INITIAL_MOVING_UP_STATE: u8 = 0x10;
+const K_ENEMY_INITIAL_MOVING_RIGHT_STATE: u8 = 0x28;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up = 0,
+    Left = 1,
+    Down = 2,
+    Right = 3,
+}
+
+impl Direction {
+    /// Directions are stored in the two lowest bits of the tile states
+    pub fn from_state(state: u8) -> Direction {
+        match state & 0x03 {
+            0 => Direction::Up,
+            1 => Direction::Left,
+            2 => Direction::Down,
+            _ => Direction::Right,
+        }
+    }
+
+    pub fn is_horizontal(self) -> bool {
+        self == Direction::Left || self == Direction::Right
+    }
+}
+
+/// Returns the position next to the given one in the given direction. Positions out of the
+/// level are returned as values greater than K_LEVEL_SIZE.
+pub fn adjacent_position(position: usize, direction: Direction) -> usize {
+    match direction {
+        Direction::Up => position.wrapping_sub(K_LEVEL_WIDTH),
+        Direction::Left => position.wrapping_sub(1),
+        Direction::Down => position + K_LEVEL_WIDTH,
+        Direction::Right => position + 1,
+    }
+}
+
+/// Builds the word of a tile with the given state
+pub fn tile_word(tile: LevelTileType, state: u8) -> u16 {
+    ((state as u16) << 8) | tile as u16
+}
+
+/// Tells if the game loop has to look at the tile. The original game uses this quick test on the tile
+/// value, which all the tiles with an update function pass, like a few others which are skipped.
+pub fn is_moving_tile(tile: u8) -> bool {
+    tile & 0x0D != 0 && tile < 0x20
+}
+
+impl GameStates {
+    /// Copies the level data into the current level state (readLevels)
+    pub fn load_level(&mut self, level: &Level) {
+        self.g_current_level = level.clone();
+
+        for i in 0..K_LEVEL_DATA_LENGTH {
+            self.g_current_level_state[i] = StatefulLevelTile::default();
+        }
+        for i in 0..K_LEVEL_SIZE {
+            self.g_current_level_state[i].tile = level.tiles[i];
+        }
+        *self.g_explosion_timers = [0; K_LEVEL_DATA_LENGTH];
+        *self.g_terminal_scroll_offsets = [0; K_LEVEL_DATA_LENGTH];
+
+        self.g_is_gravity_enabled = level.initial_gravitation;
+        self.g_are_zonks_frozen = level.freeze_zonks;
+        self.g_number_of_info_trons = level.number_of_infotrons;
+        self.g_number_of_special_ports = level.number_of_special_ports;
+        self.g_current_level_name = level.list_name();
+    }
+
+    /// Resets the game info before the level starts (initializeGameInfo)
+    pub fn initialize_game_info(&mut self) {
+        self.g_is_murphy_looking_left = false;
+        self.g_should_kill_murphy = false;
+        self.g_should_exit_level = false;
+        self.g_quit_level_countdown = 0;
+        self.g_number_of_remaining_red_disks = 0;
+        self.g_additional_info_in_game_panel_frame_counter = 0;
+        self.g_murphy_yawn_and_sleep_counter = 0;
+        self.g_last_drawn_minutes_and_seconds = 0xffff;
+        self.g_last_drawn_hours = 0xff; // 255
+        self.g_is_game_running = true;
+        self.g_aux_game_seconds_20ms_accumulator = 0;
+        self.g_game_seconds = 0;
+        self.g_game_minutes = 0;
+        self.g_game_hours = 0;
+        self.g_is_explosion_started = false;
+        self.g_terminal_max_frames_to_next_scroll = 0x7F; // 127
+        self.g_are_yellow_disks_detonated = false;
+        self.g_frame_counter = 0;
+        self.g_should_show_game_panel = true;
+        self.g_current_panel_height = K_PANEL_BITMAP_HEIGHT;
+        self.g_are_enemies_frozen = false;
+        self.g_is_murphy_going_through_portal &= 0xff00; // mov byte ptr gIsMurphyGoingThroughPortal, 0
+        self.g_planted_red_disk_countdown = 0;
+        self.g_planted_red_disk_position = 0;
+    }
+
+    /// Converts the tiles of the level to the ones the simulation works with, and starts the
+    /// enemies (convertToEasyTiles). Returns the number of infotrons found in the level.
+    pub fn convert_to_easy_tiles(&mut self) -> u16 {
+        let mut number_of_infotrons = 0;
+
+        for position in 0..K_LEVEL_SIZE {
+            let current = self.g_current_level_state[position];
+            if current.tile == K_LEVEL_FILE_EXPLOSION_TILE {
+                self.g_current_level_state[position].tile = LevelTileType::Explosion as u8;
+                continue;
+            }
+            if current.tile == LevelTileType::Infotron as u8 {
+                number_of_infotrons += 1;
+                continue;
+            }
+            if current.state != 0 {
+                continue;
+            }
+
+            if current.tile == LevelTileType::SnikSnak as u8
+                || current.tile == LevelTileType::Electron as u8
+            {
+                // Enemies start turning if there is no wall on their left, or moving up or right
+                let left = adjacent_position(position, Direction::Left);
+                let above = adjacent_position(position, Direction::Up);
+                let right = adjacent_position(position, Direction::Right);
+                if self.word_at(left) == 0 {
+                    self.g_current_level_state[position].state = K_ENEMY_INITIAL_TURNING_STATE;
+                } else if self.word_at(above) == 0 {
+                    self.g_current_level_state[above] = StatefulLevelTile {
+                        tile: current.tile,
+                        state: K_ENEMY_INITIAL_MOVING_UP_STATE,
+                    };
+                    self.set_word(position, K_OBJECT_LEAVING_WORD);
+                } else if self.word_at(right) == 0 {
+                    self.g_current_level_state[right] = StatefulLevelTile {
+                        tile: current.tile,
+                        state: K_ENEMY_INITIAL_MOVING_RIGHT_STATE,
+                    };
+                    self.set_word(position, K_OBJECT_LEAVING_WORD);
+                }
+            } else if current.tile == LevelTileType::HorizontalChipLeft as u8
+                || current.tile == LevelTileType::HorizontalChipRight as u8
+                || current.tile == LevelTileType::HorizontalChipTop as u8
+                || current.tile == LevelTileType::HorizontalChipBottom as u8
+            {
+                self.set_tile(position, LevelTileType::Chip, 0);
+            } else if current.tile >= LevelTileType::Hardware2 as u8
+                && current.tile <= LevelTileType::Hardware11 as u8
+            {
+                self.set_tile(position, LevelTileType::Hardware, 0);
+            } else if current.tile >= LevelTileType::SportRight as u8
+                && current.tile <= LevelTileType::SportUp as u8
+            {
+                // Special ports work like the regular ones, their state tells they are special
+                self.g_current_level_state[position] = StatefulLevelTile {
+                    tile: current.tile - 4,
+                    state: K_SPECIAL_PORT_STATE,
+                };
+            }
+        }
+
+        number_of_infotrons
+    }
+
+    /// Sets the number of infotrons Murphy must collect. Levels can set it, or let the game use
+    /// the number of infotrons found in the level.
+    pub fn reset_number_of_infotrons(&mut self, number_of_infotrons_found_in_level: u16) {
+        // In the original game, the number of infotrons found in a level is stored in a 2-bytes
+        // variable, however, when stored for its use in the game, it's stored in a 1-byte variable.
+        let mut number_of_infotrons = (number_of_infotrons_found_in_level & 0xFF) as u8;
+        if self.g_number_of_info_trons != 0 {
+            number_of_infotrons = self.g_number_of_info_trons;
+        }
+
+        self.g_number_of_remaining_infotrons = number_of_infotrons;
+        self.g_total_number_of_infotrons = number_of_infotrons;
+    }
+
+    /// Finds where Murphy starts in the level (findMurphy)
+    pub fn find_murphy(&mut self) {
+        if let Some(location) = self
+            .g_current_level
+            .tiles
+            .iter()
+            .position(|&tile| tile == LevelTileType::Murphy as u8)
+        {
+            self.g_murphy_location = location;
+        }
+
+        self.g_murphy_tile_x = self.g_murphy_location % K_LEVEL_WIDTH;
+        self.g_murphy_tile_y = self.g_murphy_location / K_LEVEL_WIDTH;
+        self.g_murphy_position_x = self.g_murphy_tile_x * K_TILE_SIZE;
+        self.g_murphy_position_y = self.g_murphy_tile_y * K_TILE_SIZE;
+    }
+
+    /// Runs the simulation of one frame of the level, which is the game loop of runLevel without
+    /// the rendering. Returns the random number explosions use to shake the screen.
+    pub fn update_level(&mut self, user_input: UserInput) -> u16 {
+        self.update_moving_objects(user_input);
+        self.update_planted_red_disk();
+        self.update_explosion_timers();
+
+        // The original game generates this random number for _every_ explosion, even if normally
+        // only Murphy's explosion makes the screen shake. It's necessary to do it here to keep
+        // the right sequence of random numbers when there are explosions in the level.
+        if self.g_is_explosion_started {
+            self.generate_random_number()
+        } else {
+            0
+        }
+    }
+
+    /// Counts the frame that was just played, and tells if the level is over. The level ends when
+    /// the player leaves it, or once the countdown started by Murphy's death or exit is over.
+    pub fn finish_frame(&mut self) -> bool {
+        self.g_frame_counter = self.g_frame_counter.wrapping_add(1);
+        if self.g_should_exit_level {
+            return true;
+        }
+        if self.g_quit_level_countdown == 0 {
+            return false;
+        }
+
+        self.g_quit_level_countdown -= 1;
+        self.g_quit_level_countdown == 0
+    }
+
+    /// Updates every object of the level for one frame (gameloop)
+    pub fn update_moving_objects(&mut self, user_input: UserInput) {
+        self.g_current_user_input = user_input;
+
+        let murphy_location = self.g_murphy_location;
+        self.update_murphy();
+
+        // The objects to update are listed before updating any of them, so objects moving to a
+        // tile that comes later in the level are not updated twice in the same frame
+        let moving_tiles: Vec<(usize, u8)> = (0..K_LEVEL_SIZE)
+            .map(|position| (position, self.g_current_level_state[position].tile))
+            .filter(|&(_, tile)| is_moving_tile(tile))
+            .collect();
+
+        for (position, tile) in moving_tiles {
+            match tile {
+                t if t == LevelTileType::Zonk as u8 => self.update_zonk(position),
+                t if t == LevelTileType::Infotron as u8 => self.update_infotron(position),
+                t if t == LevelTileType::OrangeDisk as u8 => self.update_orange_disk(position),
+                t if t == LevelTileType::SnikSnak as u8 || t == LevelTileType::Electron as u8 => {
+                    self.update_enemy(position, tile)
+                }
+                t if t == LevelTileType::Terminal as u8 => self.update_terminal(position),
+                t if t == LevelTileType::Bug as u8 => self.update_bug(position),
+                t if t == LevelTileType::Explosion as u8 => self.update_explosion(position),
+                _ => {}
+            }
+        }
+
+        // Murphy explodes when he was caught in an explosion, or when he disappeared
+        if (self.g_should_kill_murphy || !self.g_is_murphy_updated)
+            && self.g_quit_level_countdown == 0
+        {
+            self.g_should_kill_murphy = false;
+            self.detonate(murphy_location);
+            self.g_quit_level_countdown = K_QUIT_LEVEL_COUNTDOWN;
+        }
+
+        self.update_game_time();
+    }
+
+    /// Generates the next number of the level random sequence. The sequence must stay identical to
+    /// the original game for demos to replay the same way.
+    pub fn generate_random_number(&mut self) -> u16 {
+        let mut some_value = self.g_random_generator_seed;
+        some_value = some_value.wrapping_mul(0x5E5); // 1509
+        some_value = some_value.wrapping_add(0x31); // '1' or 49
+        self.g_random_generator_seed = some_value;
+        some_value / 2
+    }
+
+    /// Returns the word of the tile at the given position. Positions out of the level are read as
+    /// hardware, so nothing can move there.
+    pub fn word_at(&self, position: usize) -> u16 {
+        if position >= K_LEVEL_SIZE {
+            return LevelTileType::Hardware as u16;
+        }
+        let tile = self.g_current_level_state[position];
+        ((tile.state as u16) << 8) | tile.tile as u16
+    }
+
+    /// Returns the tile at the given position, without its state
+    pub fn tile_at(&self, position: usize) -> u8 {
+        (self.word_at(position) & 0xFF) as u8
+    }
+
+    pub fn set_word(&mut self, position: usize, word: u16) {
+        if position >= K_LEVEL_SIZE {
+            return;
+        }
+        self.g_current_level_state[position] = StatefulLevelTile {
+            tile: (word & 0xFF) as u8,
+            state: (word >> 8) as u8,
+        };
+    }
+
+    pub fn set_state(&mut self, position: usize, state: u8) {
+        if position < K_LEVEL_SIZE {
+            self.g_current_level_state[position].state = state;
+        }
+    }
+
+    pub fn set_tile(&mut self, position: usize, tile: LevelTileType, state: u8) {
+        self.set_word(position, tile_word(tile, state));
+    }
+
+    /// Empties the tile at the given position, unless an explosion is going on there
+    pub fn clear_tile_unless_exploding(&mut self, position: usize) {
+        if self.tile_at(position) != LevelTileType::Explosion as u8 {
+            self.set_word(position, 0);
+        }
+    }
+
+    /// Sound effects are queued during the simulation and played by the game afterwards
+    pub fn queue_sound_effect(&mut self, sound_effect: SoundEffect) {
+        self.g_pending_sound_effects.push(sound_effect);
+    }
+
+    fn update_game_time(&mut self) {
+        if !self.g_is_game_running {
+            return;
+        }
+
+        self.g_aux_game_seconds_20ms_accumulator += 1;
+        if self.g_aux_game_seconds_20ms_accumulator < K_GAME_FRAMES_PER_SECOND {
+            return;
+        }
+        self.g_aux_game_seconds_20ms_accumulator = 0;
+
+        self.g_game_seconds += 1;
+        if self.g_game_seconds < 60 {
+            return;
+        }
+        self.g_game_seconds = 0;
+
+        self.g_game_minutes += 1;
+        if self.g_game_minutes < 60 {
+            return;
+        }
+        self.g_game_minutes = 0;
+
+        self.g_game_hours = self.g_game_hours.wrapping_add(1);
+    }
+
+    /// Counts down to the explosion of the red disk Murphy released (updatePlantedRedDisk). The
+    /// countdown is 1 while Murphy is still holding the disk.
+    pub fn update_planted_red_disk(&mut self) {
+        if self.g_planted_red_disk_countdown <= 1 {
+            return;
+        }
+
+        // The disk becomes a tile of its own once Murphy left it
+        let position = self.g_planted_red_disk_position as usize;
+        if self.word_at(position) == 0 {
+            self.set_tile(position, LevelTileType::RedDisk, 0);
+        }
+
+        self.g_planted_red_disk_countdown += 1;
+        if self.g_planted_red_disk_countdown < K_PLANTED_RED_DISK_EXPLOSION_COUNTDOWN {
+            return;
+        }
+
+        self.detonate(position);
+        self.g_planted_red_disk_countdown = 0;
+    }
+}
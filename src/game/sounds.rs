@@ -56,6 +56,7 @@ pub enum SoundType {
     Roland = 5,
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum SoundEffect {
     Explosion,
     Infotron,
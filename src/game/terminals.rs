@@ -0,0 +1,66 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Terminals (updateTerminalTiles) and the yellow disks they detonate.
+//
+// The state of a terminal counts the frames until its screen scrolls by one line, as a signed
+// value. After each scroll, it restarts from a random negative value bounded by
+// g_terminal_max_frames_to_next_scroll, which goes down once Murphy used a terminal.
+
+use crate::game::game_states::GameStates;
+use crate::game::globals::*;
+
+// Frames between two scrolls of the terminal screens after Murphy used one of them
+const K_USED_TERMINAL_MAX_FRAMES_TO_NEXT_SCROLL: u8 = 0x07;
+
+impl GameStates {
+    /// Scrolls the screen of the terminal at the given position when its countdown is over
+    pub fn update_terminal(&mut self, position: usize) {
+        if self.tile_at(position) != LevelTileType::Terminal as u8 {
+            return;
+        }
+
+        let state = (self.g_current_level_state[position].state as i8).wrapping_add(1);
+        if state <= 0 {
+            self.set_state(position, state as u8);
+            return;
+        }
+
+        let random_number = self.generate_random_number() as u8;
+        self.set_state(
+            position,
+            (random_number & self.g_terminal_max_frames_to_next_scroll).wrapping_neg(),
+        );
+        self.g_terminal_scroll_offsets[position] =
+            self.g_terminal_scroll_offsets[position].wrapping_add(1);
+    }
+
+    /// Murphy touched a terminal: the first time, every yellow disk of the level explodes
+    pub fn use_terminal(&mut self) {
+        if self.g_are_yellow_disks_detonated {
+            return;
+        }
+        self.g_terminal_max_frames_to_next_scroll = K_USED_TERMINAL_MAX_FRAMES_TO_NEXT_SCROLL;
+        self.g_are_yellow_disks_detonated = true;
+
+        for position in 0..K_LEVEL_SIZE {
+            if self.word_at(position) == LevelTileType::YellowDisk as u16 {
+                self.detonate(position);
+            }
+        }
+    }
+}
@@ -28,3 +28,76 @@ pub fn convert_16le(value: u16) -> u16 {
 pub fn clamp<T: Ord>(v: T, a: T, b: T) -> T {
     std::cmp::min(std::cmp::max(a, v), b)
 }
+
+/// Writes bytes as a quoted text, with \r, \n, \", \\ and \xHH escapes for the other bytes
+pub fn escape_text(bytes: &[u8]) -> String {
+    quote_text(bytes.iter().map(|&byte| byte as char), |character| {
+        (!(' '..='~').contains(&character)).then(|| format!("\\x{:02X}", character as u32))
+    })
+}
+
+/// Writes a text as a JSON string, with \r, \n, \", \\ and \uHHHH escapes for the other control
+/// characters
+pub fn escape_json_string(text: &str) -> String {
+    quote_text(text.chars(), |character| {
+        character
+            .is_ascii_control()
+            .then(|| format!("\\u{:04x}", character as u32))
+    })
+}
+
+/// Quotes a text, escaping \r, \n, \" and \\ and the characters escape_character returns an escape
+/// sequence for
+fn quote_text(
+    characters: impl Iterator<Item = char>,
+    escape_character: impl Fn(char) -> Option<String>,
+) -> String {
+    let mut text = String::from("\"");
+    for character in characters {
+        match character {
+            '\r' => text.push_str("\\r"),
+            '\n' => text.push_str("\\n"),
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            character => match escape_character(character) {
+                Some(escape_sequence) => text.push_str(&escape_sequence),
+                None => text.push(character),
+            },
+        }
+    }
+    text.push('"');
+    text
+}
+
+/// Reads the bytes of a quoted text written by escape_text
+pub fn unescape_text(value: &str) -> Result<Vec<u8>, String> {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or("the text must be between quotes")?;
+
+    let mut bytes = Vec::new();
+    let mut characters = value.bytes();
+    while let Some(byte) = characters.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match characters.next() {
+            Some(b'r') => bytes.push(b'\r'),
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b'"') => bytes.push(b'"'),
+            Some(b'\\') => bytes.push(b'\\'),
+            Some(b'x') => {
+                let hex: Vec<u8> = characters.by_ref().take(2).collect();
+                let byte = std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or("invalid escape sequence in the text")?;
+                bytes.push(byte);
+            }
+            _ => return Err(String::from("invalid escape sequence in the text")),
+        }
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,448 @@
+/*
+* This file is part of the Rustaplex application (https://github.com/leirn/rustaplex).
+* Copyright (c) 2022 Laurent Vromman <leirn@vromman.org>
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful, but
+* WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+* General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+// Headless demo verification, like the "@" option of the speed fix versions. The demo is played as
+// fast as possible without any SDL context, and the outcome tells if it solves its level.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::game::demo::{DemoChecksumValidation, DemoManager};
+use crate::game::game_states::GameStates;
+use crate::game::globals::*;
+use crate::game::keyboard::UserInput;
+use crate::game::level::LevelManager;
+use crate::game::utils::escape_json_string;
+
+pub struct DemoVerificationResult {
+    pub level_name: String,
+    pub random_seed: u16,
+    pub is_level_solved: bool,
+    pub number_of_frames: u32,
+    pub game_hours: u8,
+    pub game_minutes: u8,
+    pub game_seconds: u8,
+    pub number_of_remaining_infotrons: u8,
+    pub checksum_validation: Option<DemoChecksumValidation>,
+}
+
+/// Filename and verification result of each demo of a directory
+pub type DemoDirectoryVerificationResults = Vec<(String, Result<DemoVerificationResult, String>)>;
+
+impl fmt::Display for DemoVerificationResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Level: {}", self.level_name)?;
+        writeln!(f, "Random seed: 0x{:04X}", self.random_seed)?;
+        writeln!(
+            f,
+            "Outcome: {}",
+            if self.is_level_solved {
+                "success"
+            } else {
+                "failure"
+            }
+        )?;
+        writeln!(f, "Frames: {}", self.number_of_frames)?;
+        writeln!(
+            f,
+            "Game time: {:02}:{:02}:{:02}",
+            self.game_hours, self.game_minutes, self.game_seconds
+        )?;
+        writeln!(
+            f,
+            "Remaining infotrons: {}",
+            self.number_of_remaining_infotrons
+        )?;
+        match &self.checksum_validation {
+            Some(validation) => write!(
+                f,
+                "Checksum: {} (slowest speed {}, stored 0x{:02X}, computed 0x{:02X})",
+                if validation.is_valid() {
+                    "valid"
+                } else {
+                    "invalid"
+                },
+                validation.slowest_speed,
+                validation.stored_checksum,
+                validation.computed_checksum
+            ),
+            None => write!(f, "Checksum: none, the demo doesn't include its level"),
+        }
+    }
+}
+
+/// Plays the demo of the given .SP or DEMOx.BIN file until it ends. Demos that don't include their
+/// level are played on the level of LEVELS.DAT with their level number.
+pub fn verify_demo(filename: &str) -> Result<DemoVerificationResult, String> {
+    let mut demo_manager = DemoManager::new();
+    demo_manager.demo_file_name = filename.to_string();
+    demo_manager.g_is_sp_demo_available_to_run = 1;
+    if demo_manager.read_demo_files() == 0 {
+        return Err(format!("{} is not a valid demo file", filename));
+    }
+
+    let mut level_manager = LevelManager::new();
+    if !demo_manager.g_demos.includes_level(0) {
+        level_manager.read_level_set(&format!(
+            "{}/{}",
+            RESSOURCES_PATH, level_manager.g_levels_dat_filename
+        ))?;
+    }
+    if !demo_manager.prepare_demo_playback(0, level_manager.g_number_of_levels) {
+        return Err(format!(
+            "{} is not a valid demo file, or its level isn't in the level set",
+            filename
+        ));
+    }
+
+    let level = if demo_manager.is_original_demo() {
+        let level_number = demo_manager.g_demo_index_or_demo_level_number as usize;
+        level_manager.g_level_list_data[level_number - 1].clone()
+    } else {
+        demo_manager.g_demos.level[0].clone()
+    };
+
+    let mut states = GameStates::new();
+    states.g_random_generator_seed = demo_manager.g_demo_random_seeds[0];
+    states.g_is_playing_demo = true;
+    states.g_is_level_started_as_demo = true;
+    states.load_level(&level);
+    states.initialize_game_info();
+    let number_of_infotrons = states.convert_to_easy_tiles();
+    states.reset_number_of_infotrons(number_of_infotrons);
+    states.find_murphy();
+
+    let mut number_of_frames = 0;
+    loop {
+        let user_input = match demo_manager.next_demo_input() {
+            Some(user_input) => user_input,
+            None => {
+                // The level ends with the demo, unless Murphy is already leaving it
+                if states.g_quit_level_countdown == 0 {
+                    states.g_should_exit_level = true;
+                }
+                UserInput::None
+            }
+        };
+        states.update_level(user_input);
+        states.g_pending_sound_effects.clear();
+
+        number_of_frames += 1;
+        if states.finish_frame() {
+            break;
+        }
+    }
+
+    Ok(DemoVerificationResult {
+        level_name: level.name.trim().to_string(),
+        random_seed: demo_manager.g_demo_random_seeds[0],
+        is_level_solved: states.g_current_player_level_state == PlayerLevelState::Completed,
+        number_of_frames,
+        game_hours: states.g_game_hours,
+        game_minutes: states.g_game_minutes,
+        game_seconds: states.g_game_seconds,
+        number_of_remaining_infotrons: states.g_number_of_remaining_infotrons,
+        checksum_validation: demo_manager.g_demos.validate_checksum(0),
+    })
+}
+
+/// Verifies the demos of all the .SP files of the given directory, sorted by filename. A demo that
+/// can't be read doesn't stop the other ones from being verified.
+pub fn verify_demo_directory(directory: &str) -> Result<DemoDirectoryVerificationResults, String> {
+    let entries = fs::read_dir(directory)
+        .map_err(|error| format!("Can't read the directory {}: {}", directory, error))?;
+    let mut filenames: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("sp"))
+        })
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    filenames.sort();
+
+    Ok(filenames
+        .into_iter()
+        .map(|filename| {
+            let result = verify_demo(&filename);
+            (filename, result)
+        })
+        .collect())
+}
+
+/// Writes the results of verify_demo_directory as a JSON report
+pub fn demo_verification_report_to_json(results: &DemoDirectoryVerificationResults) -> String {
+    let number_of_solved_demos = results
+        .iter()
+        .filter(|(_, result)| result.as_ref().is_ok_and(|result| result.is_level_solved))
+        .count();
+
+    let demos = results
+        .iter()
+        .map(|(filename, result)| {
+            let filename = Path::new(filename)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let mut fields = vec![("file", JsonValue::String(filename))];
+            match result {
+                Ok(result) => fields.extend([
+                    ("level_name", JsonValue::String(result.level_name.clone())),
+                    ("completed", JsonValue::Bool(result.is_level_solved)),
+                    ("frames", JsonValue::Number(result.number_of_frames as u64)),
+                    (
+                        "game_time",
+                        JsonValue::String(format!(
+                            "{:02}:{:02}:{:02}",
+                            result.game_hours, result.game_minutes, result.game_seconds
+                        )),
+                    ),
+                    ("random_seed", JsonValue::Number(result.random_seed as u64)),
+                    (
+                        "checksum_valid",
+                        match &result.checksum_validation {
+                            Some(validation) => JsonValue::Bool(validation.is_valid()),
+                            None => JsonValue::Null,
+                        },
+                    ),
+                ]),
+                Err(error) => fields.push(("error", JsonValue::String(error.clone()))),
+            }
+            JsonValue::Object(fields)
+        })
+        .collect();
+
+    let report = JsonValue::Object(vec![
+        ("number_of_demos", JsonValue::Number(results.len() as u64)),
+        (
+            "number_of_solved_demos",
+            JsonValue::Number(number_of_solved_demos as u64),
+        ),
+        ("demos", JsonValue::Array(demos)),
+    ]);
+    let mut json = String::new();
+    report.write(&mut json, 0);
+    json.push('\n');
+    json
+}
+
+/// The values of the JSON report
+enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(&'static str, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Writes the value at the given indentation level. Arrays and objects holding other arrays or
+    /// objects are written one item per line, the other ones on a single line.
+    fn write(&self, json: &mut String, indentation: usize) {
+        let items: Vec<(Option<&str>, &JsonValue)> = match self {
+            JsonValue::Null => return json.push_str("null"),
+            JsonValue::Bool(value) => return json.push_str(&value.to_string()),
+            JsonValue::Number(value) => return json.push_str(&value.to_string()),
+            JsonValue::String(value) => return json.push_str(&escape_json_string(value)),
+            JsonValue::Array(items) => items.iter().map(|item| (None, item)).collect(),
+            JsonValue::Object(fields) => fields
+                .iter()
+                .map(|(name, value)| (Some(*name), value))
+                .collect(),
+        };
+        let (opening, closing) = match self {
+            JsonValue::Array(_) => ('[', ']'),
+            _ => ('{', '}'),
+        };
+        let is_multiline = items
+            .iter()
+            .any(|(_, value)| matches!(value, JsonValue::Array(_) | JsonValue::Object(_)));
+
+        json.push(opening);
+        for (index, (name, value)) in items.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            if is_multiline {
+                json.push('\n');
+                json.push_str(&"  ".repeat(indentation + 1));
+            } else if index > 0 {
+                json.push(' ');
+            }
+            if let Some(name) = name {
+                json.push_str(&escape_json_string(name));
+                json.push_str(": ");
+            }
+            value.write(json, indentation + 1);
+        }
+        if is_multiline {
+            json.push('\n');
+            json.push_str(&"  ".repeat(indentation));
+        }
+        json.push(closing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::demo::K_DEMO_END_MARKER;
+    use crate::game::simulation::K_QUIT_LEVEL_COUNTDOWN;
+
+    fn verify_shipped_demo(demo_index: usize) -> DemoVerificationResult {
+        let filename = format!(
+            "{}/{}/DEMO{}.BIN",
+            env!("CARGO_MANIFEST_DIR"),
+            RESSOURCES_PATH,
+            demo_index
+        );
+        verify_demo(&filename).unwrap_or_else(|error| panic!("DEMO{}: {}", demo_index, error))
+    }
+
+    #[test]
+    fn demos_without_end_marker_or_valid_level_number_are_handled() {
+        let directory =
+            std::env::temp_dir().join(format!("rustaplex-demos-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        // Level 1 without end marker: the demo ends with its data
+        let unterminated_demo = directory.join("UNTERMINATED.SP");
+        fs::write(
+            &unterminated_demo,
+            [0x01, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20],
+        )
+        .unwrap();
+        let result = verify_demo(&unterminated_demo.to_string_lossy()).unwrap();
+        assert!(!result.is_level_solved);
+
+        // A demo for level 0 can't be played without its level
+        let level_zero_demo = directory.join("ZERO.SP");
+        fs::write(&level_zero_demo, [0; 10]).unwrap();
+        assert!(verify_demo(&level_zero_demo.to_string_lossy()).is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    /// Number of frames the inputs of a shipped demo last, from its first input to its end marker
+    fn recorded_number_of_frames(demo_index: usize) -> u32 {
+        let demo_file_data = fs::read(format!(
+            "{}/{}/DEMO{}.BIN",
+            env!("CARGO_MANIFEST_DIR"),
+            RESSOURCES_PATH,
+            demo_index
+        ))
+        .unwrap();
+        demo_file_data[1..]
+            .iter()
+            .take_while(|&&demo_byte| demo_byte != K_DEMO_END_MARKER)
+            .map(|demo_byte| (demo_byte >> 4) as u32 + 1)
+            .sum()
+    }
+
+    #[test]
+    fn first_shipped_demo_solves_its_level() {
+        let result = verify_shipped_demo(0);
+        assert_eq!(result.level_name, "------- WARM UP -------");
+        assert!(result.is_level_solved);
+        assert_eq!(result.number_of_remaining_infotrons, 0);
+    }
+
+    #[test]
+    fn shipped_demos_are_replayed_until_the_end_of_their_recording() {
+        // The original game recorded the demos until their level ended, so replaying them in sync
+        // ends the level with their last input, or during the frames Murphy's death or exit let
+        // the game run. Levels 1 and 55 also check bugs and explosions keep the demos in sync.
+        // DEMO8 still goes out of sync: Murphy dies long before the end of its recording.
+        for demo_index in (0..10).filter(|&demo_index| demo_index != 8) {
+            let recorded_number_of_frames = recorded_number_of_frames(demo_index);
+            let number_of_frames = verify_shipped_demo(demo_index).number_of_frames;
+            assert!(
+                number_of_frames > recorded_number_of_frames
+                    && number_of_frames
+                        <= recorded_number_of_frames + K_QUIT_LEVEL_COUNTDOWN as u32,
+                "DEMO{} lasts {} frames, its recording {}",
+                demo_index,
+                number_of_frames,
+                recorded_number_of_frames
+            );
+        }
+    }
+
+    #[test]
+    fn shipped_demo_lasts_as_long_as_in_the_original_game() {
+        // SPFIX63.DOC gives 2 minutes and 9 (+/-1) seconds on the game panel at the end of the demo
+        // started with F2 (DEMO1), at the original speed the game time is counted in
+        let result = verify_shipped_demo(1);
+        let game_time = result.game_minutes as i32 * 60 + result.game_seconds as i32;
+        assert_eq!(result.game_hours, 0);
+        assert!(
+            (game_time - (2 * 60 + 9)).abs() <= 1,
+            "{} seconds",
+            game_time
+        );
+    }
+
+    #[test]
+    fn json_report_is_parsed_back() {
+        let results: DemoDirectoryVerificationResults = vec![
+            (
+                String::from("demos/\"Quoted\" d\u{e9}mo\u{1}.SP"),
+                Ok(DemoVerificationResult {
+                    level_name: String::from("\\ L\u{e9}vel \"1\"\t"),
+                    random_seed: 0x1234,
+                    is_level_solved: true,
+                    number_of_frames: 2263,
+                    game_hours: 0,
+                    game_minutes: 1,
+                    game_seconds: 2,
+                    number_of_remaining_infotrons: 0,
+                    checksum_validation: None,
+                }),
+            ),
+            (
+                String::from("demos/\u{fffd}BAD.SP"),
+                Err(String::from("Can't read \"\u{fffd}BAD.SP\"\r\n")),
+            ),
+        ];
+
+        let json = demo_verification_report_to_json(&results);
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(report["number_of_demos"], 2);
+        assert_eq!(report["number_of_solved_demos"], 1);
+
+        let demos = report["demos"].as_array().unwrap();
+        assert_eq!(demos.len(), 2);
+        assert_eq!(demos[0]["file"], "\"Quoted\" d\u{e9}mo\u{1}.SP");
+        assert_eq!(demos[0]["level_name"], "\\ L\u{e9}vel \"1\"\t");
+        assert_eq!(demos[0]["completed"], true);
+        assert_eq!(demos[0]["frames"], 2263);
+        assert_eq!(demos[0]["game_time"], "00:01:02");
+        assert_eq!(demos[0]["random_seed"], 0x1234);
+        assert!(demos[0]["checksum_valid"].is_null());
+        assert_eq!(demos[1]["file"], "\u{fffd}BAD.SP");
+        assert_eq!(demos[1]["error"], "Can't read \"\u{fffd}BAD.SP\"\r\n");
+
+        let empty_report: serde_json::Value =
+            serde_json::from_str(&demo_verification_report_to_json(&Vec::new())).unwrap();
+        assert_eq!(empty_report["demos"].as_array().unwrap().len(), 0);
+    }
+}
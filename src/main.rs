@@ -16,19 +16,179 @@
 */
 
 mod game;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use game::Game;
 use log::info;
 use std::env;
+use std::fs;
+use std::process;
 use std::thread;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Log level. Can be any value within debug, info, warn, error, critical, none
     #[arg(short, long)]
     loglevel: Option<String>,
-    // TODO : add custom level file
+    /// Plays the demo of a .SP or DEMOx.BIN file as fast as possible without opening any window,
+    /// and tells if it solves its level (the "@" option of the speed fix versions)
+    #[arg(long, value_name = "DEMO_FILE")]
+    verify: Option<String>,
+    /// Shows the input, repeat count, demo byte and frame counter over the game panel while demos
+    /// are played
+    #[arg(long)]
+    demo_overlay: bool,
+    /// .SP file to run instead of the main menu. Files longer than a level include a demo, which
+    /// is played, otherwise the level is started right away.
+    #[arg(value_name = "SP_FILE")]
+    sp_file: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Converts a .SP or DEMOx.BIN demo into a readable list of moves
+    DemoToText {
+        demo_file: String,
+        /// Text file to write, the moves are printed when it is not given
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Compiles a list of moves written by demo-to-text back into a .SP or DEMOx.BIN demo
+    TextToDemo { text_file: String, output: String },
+    /// Reads a level set, a LEVELS.DAT, a MPX or a text file, and writes it into another file. Text
+    /// files have the .TXT extension. The output is a MPX file when its extension is .MPX, a text
+    /// file when it is .TXT, and a LEVELS.DAT file otherwise.
+    ConvertLevels { input: String, output: String },
+    /// Verifies the demos of all the .SP files of a directory and writes a JSON report. Exits with
+    /// 0 if all of them solve their level, 1 otherwise.
+    VerifyBatch {
+        directory: String,
+        /// JSON file to write, the report is printed when it is not given
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+/// Runs a subcommand and returns its exit code
+fn run_command(command: Command) -> i32 {
+    let result = match command {
+        Command::VerifyBatch { directory, output } => {
+            return run_demo_batch_verification(&directory, output)
+        }
+        Command::DemoToText { demo_file, output } => fs::read(&demo_file)
+            .map_err(|error| format!("Can't read {}: {}", demo_file, error))
+            .and_then(|demo_file_data| game::demo_text::demo_to_text(&demo_file_data))
+            .and_then(|text| match output {
+                Some(output) => fs::write(&output, text)
+                    .map_err(|error| format!("Can't write {}: {}", output, error)),
+                None => {
+                    print!("{}", text);
+                    Ok(())
+                }
+            }),
+        Command::TextToDemo { text_file, output } => fs::read_to_string(&text_file)
+            .map_err(|error| format!("Can't read {}: {}", text_file, error))
+            .and_then(|text| game::demo_text::text_to_demo(&text))
+            .and_then(|demo_file_data| {
+                fs::write(&output, demo_file_data)
+                    .map_err(|error| format!("Can't write {}: {}", output, error))
+            }),
+        Command::ConvertLevels { input, output } => {
+            let mut level_manager = game::level::LevelManager::new();
+            level_manager
+                .read_level_set(&input)
+                .and_then(|_| level_manager.write_level_set(&output))
+        }
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("{}", error);
+            2
+        }
+    }
+}
+
+/// Returns the exit code of the verification of the demos of a directory: 0 if all of them solve
+/// their level, 1 if any doesn't or can't be read, and 2 if the directory can't be read or the
+/// report can't be written
+fn run_demo_batch_verification(directory: &str, output: Option<String>) -> i32 {
+    let results = match game::verification::verify_demo_directory(directory) {
+        Ok(results) => results,
+        Err(error) => {
+            eprintln!("{}", error);
+            return 2;
+        }
+    };
+
+    let report = game::verification::demo_verification_report_to_json(&results);
+    match output {
+        Some(output) => {
+            if let Err(error) = fs::write(&output, report) {
+                eprintln!("Can't write {}: {}", output, error);
+                return 2;
+            }
+        }
+        None => print!("{}", report),
+    }
+
+    // The list of demos tells which ones solve their level and which ones were tampered with
+    for (filename, result) in &results {
+        match result {
+            Ok(result) => eprintln!(
+                "{}: {}, checksum {}",
+                filename,
+                if result.is_level_solved {
+                    "solved"
+                } else {
+                    "not solved"
+                },
+                match &result.checksum_validation {
+                    Some(validation) if validation.is_valid() => "valid",
+                    Some(_) => "invalid",
+                    None => "absent",
+                }
+            ),
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+
+    let number_of_solved_demos = results
+        .iter()
+        .filter(|(_, result)| result.as_ref().is_ok_and(|result| result.is_level_solved))
+        .count();
+    eprintln!(
+        "{} of {} demos solve their level",
+        number_of_solved_demos,
+        results.len()
+    );
+    if number_of_solved_demos == results.len() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Returns the exit code of the demo verification: 0 if the demo solves its level, 1 if it doesn't,
+/// and 2 if the demo can't be read
+fn run_demo_verification(demo_filename: &str) -> i32 {
+    match game::verification::verify_demo(demo_filename) {
+        Ok(result) => {
+            println!("{}", result);
+            if result.is_level_solved {
+                0
+            } else {
+                1
+            }
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            2
+        }
+    }
 }
 
 fn main() {
@@ -56,12 +216,35 @@ fn main() {
 
     info!("Start Rustaplex 0.1");
 
+    if let Some(command) = args.command {
+        let child = thread::Builder::new()
+            .stack_size(32 * 1024 * 1024)
+            .spawn(move || run_command(command))
+            .unwrap();
+        process::exit(child.join().unwrap());
+    }
+
+    if let Some(demo_filename) = args.verify {
+        let child = thread::Builder::new()
+            .stack_size(32 * 1024 * 1024)
+            .spawn(move || run_demo_verification(&demo_filename))
+            .unwrap();
+        process::exit(child.join().unwrap());
+    }
+
     let child = thread::Builder::new().stack_size(32 * 1024 * 1024).spawn(move || {
-        return Game::new().start();
+        let mut game = Game::new();
+        if args.demo_overlay {
+            game.show_demo_overlay();
+        }
+        if let Some(sp_filename) = args.sp_file {
+            if let Err(error) = game.run_sp_file(&sp_filename) {
+                eprintln!("{}", error);
+                process::exit(2);
+            }
+        }
+        game.start()
     }).unwrap();
 
-    let matches = child.join().unwrap();
-
-    //let mut game = Box::new(Game::new());
-    //game.start();
+    child.join().unwrap();
 }